@@ -1,43 +1,113 @@
-use std::{collections::HashMap, io::{self, Write}};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use colored::Color;
+use clap::{Parser, ValueEnum};
 use rusty_wave_function_collapse::{
-    adjacency_graph::AdjacencyGraph,
-    renderer::AsciiRenderer,
-    rules::{adjacency_rule::AdjacencyRule, Rule},
-    traits::{AsciiRenderable, ColorRenderable},
-    types::{PossibleValues, Tile, TileType},
+    definition::{AsciiTile, PresetDefinition, TileSetDefinition},
+    entropy::{EntropyQueue, Measure},
+    error::WfcError,
+    grid::Grid,
+    image_renderer::ImageRenderer,
+    renderer::{debug_render, simple_render, AsciiRenderer},
+    selection::{GrowFromCollapsed, RandomOrder, Scanline},
+    types::{PossibleValues, TileType},
     wfc::WFC,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct AsciiTile {
-    pub id: char,
-    pub color: Color,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RendererChoice {
+    /// Redraw the grid after every step
+    Animated,
+    /// Only print the solved grid
+    Final,
+    /// Print the remaining tiles of every cell after every step
+    Debug,
 }
 
-impl std::hash::Hash for AsciiTile {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Selection {
+    /// Lowest Shannon entropy first
+    Entropy,
+    /// Fewest remaining tiles first
+    RemainingValues,
+    /// Row by row
+    Scanline,
+    /// In a random order
+    Random,
+    /// Outwards from the presets
+    Grow,
 }
 
-impl TileType for AsciiTile {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// One character per cell, one line per row
+    Text,
+    Png,
+}
 
-impl AsciiRenderable for AsciiTile {
-    fn get_ascii_representation(&self) -> char {
-        return self.id;
-    }
+/// Generates a map from a tile set file with the Wave Function Collapse
+/// algorithm.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Tile set definition, a .toml or .json file
+    #[arg(default_value = "tilesets/islands.toml")]
+    tile_set: PathBuf,
+    /// Overrides the width of the tile set file
+    #[arg(long)]
+    width: Option<usize>,
+    /// Overrides the height of the tile set file
+    #[arg(long)]
+    height: Option<usize>,
+    /// Makes the output reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Places a tile before generating, as `x,y=Name`. Can be repeated
+    #[arg(long = "preset", value_name = "X,Y=NAME", value_parser = parse_preset)]
+    presets: Vec<PresetDefinition>,
+    /// Writes the solved grid to this file
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Format of the output file, guessed from its extension by default
+    #[arg(long)]
+    format: Option<OutputFormat>,
+    /// Size in pixels of a tile in png output
+    #[arg(long, default_value_t = 8)]
+    tile_size: u32,
+    #[arg(long, value_enum, default_value_t = RendererChoice::Animated)]
+    renderer: RendererChoice,
+    /// How the next cell to observe is chosen
+    #[arg(long, value_enum, default_value_t = Selection::Entropy)]
+    selection: Selection,
+    /// How many times to start over when generation fails
+    #[arg(long, default_value_t = 0)]
+    max_retries: u32,
+    /// Starts generating without waiting for Enter
+    #[arg(long)]
+    no_wait: bool,
 }
 
-impl ColorRenderable for AsciiTile {
-    fn get_color(&self) -> Color {
-        return self.color;
-    }
+fn parse_preset(value: &str) -> Result<PresetDefinition, String> {
+    let invalid = || format!("expected x,y=Name, got {}", value);
+    let (coordinates, tile) = value.split_once('=').ok_or_else(invalid)?;
+    let (x, y) = coordinates.split_once(',').ok_or_else(invalid)?;
+    Ok(PresetDefinition {
+        x: x.trim().parse().map_err(|_| invalid())?,
+        y: y.trim().parse().map_err(|_| invalid())?,
+        tile: tile.trim().to_string(),
+    })
 }
 
 /// Computes and prints the probability breakdown of each tile type
-pub fn analyze_initial_tile_probabilities<T: TileType>(possible_tiles: &PossibleValues<T>) {
+pub fn analyze_initial_tile_probabilities<T: TileType>(
+    possible_tiles: &PossibleValues<T>,
+    wait: bool,
+) {
     let total_weight: f64 = possible_tiles.iter().map(|tile| tile.weight as f64).sum();
 
     println!("\n Initial Tile Probability Breakdown:");
@@ -52,79 +122,128 @@ pub fn analyze_initial_tile_probabilities<T: TileType>(possible_tiles: &Possible
         println!("  {}: {:.2}%", tile_name, probability);
     }
 
-    // Wait for user input before continuing
-    print!("\nPress Enter to start the algorithm...");
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut String::new()).unwrap();
+    if wait {
+        // Wait for user input before continuing
+        print!("\nPress Enter to start the algorithm...");
+        io::stdout().flush().unwrap();
+        io::stdin().read_line(&mut String::new()).unwrap();
+    }
+}
+
+fn generate(
+    wfc: &mut WFC<AsciiTile, AsciiRenderer>,
+    renderer: RendererChoice,
+) -> Result<(), WfcError> {
+    match renderer {
+        RendererChoice::Animated => wfc.run(),
+        RendererChoice::Final => {
+            wfc.run()?;
+            simple_render(&wfc.grid);
+            Ok(())
+        }
+        RendererChoice::Debug => {
+            let mut steps = wfc.steps();
+            while let Some(event) = steps.next() {
+                println!("{:?}", event?);
+                debug_render(steps.grid());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn write_output(
+    grid: &Grid<AsciiTile>,
+    path: &Path,
+    format: OutputFormat,
+    tile_size: u32,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Text => {
+            let mut text = String::new();
+            for row in grid.rows(0) {
+                for cell in row {
+                    text.push(cell.get_collapsed_value().map_or('#', |tile| tile.id.id));
+                }
+                text.push('\n');
+            }
+            fs::write(path, text).map_err(|err| err.to_string())
+        }
+        OutputFormat::Png => ImageRenderer::new(path, tile_size)
+            .save(grid)
+            .map_err(|err| err.to_string()),
+    }
 }
 
+fn main() -> ExitCode {
+    let args = Args::parse();
 
-fn main() {
-    let grass = Tile::new(
-        self::AsciiTile {
-            id: 'g',
-            color: Color::Green,
-        },
-        "Grass",
-        2,
-    );
-    let beach = Tile::new(
-        self::AsciiTile {
-            id: '.',
-            color: Color::Yellow,
-        },
-        "Beach",
-        1,
-    );
-    let water = Tile::new(
-        self::AsciiTile {
-            id: '~',
-            color: Color::Blue,
-        },
-        "Water",
-        2,
-    );
-    let hills = Tile::new(
-        self::AsciiTile {
-            id: '^',
-            color: Color::White,
-        },
-        "Hills",
-        2,
-    );
-    let tile_types: PossibleValues<AsciiTile> = vec![
-        beach.clone(),
-        grass.clone(),
-        water.clone(),
-        hills.clone(),
-    ]
-    .into_iter()
-    .collect();
-
-    let mut adj_graph = AdjacencyGraph::new();
-    adj_graph.add_self_adjacencies(vec![&grass, &water, &beach, &hills]);
-    adj_graph.add_adjacency(&grass, &beach);
-    adj_graph.add_adjacency(&beach, &water);
-    let adj_rule = AdjacencyRule::new(adj_graph);
-    let renderer = Some(AsciiRenderer);
-    let rules: Vec<Box<dyn Rule<AsciiTile>>> = vec![
-        Box::new(adj_rule),
-    ];
-    analyze_initial_tile_probabilities(&tile_types);
-    let grid_size = 15;
-    let mut wfc = WFC::new(grid_size, grid_size+10, tile_types, rules, renderer);
-    wfc.preset_tile(water.clone(), 0, 0);
-    wfc.preset_tile(water.clone(), 5, 0);
-    wfc.preset_tile(water.clone(), 0, 5);
-    wfc.preset_tile(grass.clone(), 0, 10);
-    wfc.preset_tile(grass.clone(), 10, 0);
-    wfc.preset_tile(grass.clone(), 10, 10);
-
-    match wfc.run() {
-        Ok(_) => {
+    let mut definition = match TileSetDefinition::load(&args.tile_set) {
+        Ok(definition) => definition,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
         }
+    };
+    definition.width = args.width.unwrap_or(definition.width);
+    definition.height = args.height.unwrap_or(definition.height);
+    definition.presets.extend(args.presets.iter().cloned());
+    let tile_set = match definition.build() {
+        Ok(tile_set) => tile_set,
         Err(err) => {
-            println!("Error: {}", err);
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    analyze_initial_tile_probabilities(&tile_set.tiles, !args.no_wait);
+
+    for attempt in 0..=args.max_retries {
+        let renderer = match args.renderer {
+            RendererChoice::Animated => Some(AsciiRenderer::default()),
+            RendererChoice::Final | RendererChoice::Debug => None,
+        };
+        let mut wfc = match tile_set.build_wfc(renderer) {
+            Ok(wfc) => wfc,
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Some(seed) = args.seed {
+            // Every attempt gets its own seed, the run stays reproducible
+            wfc = wfc.with_seed(seed.wrapping_add(attempt as u64));
+        }
+        wfc = match args.selection {
+            Selection::Entropy => wfc,
+            Selection::RemainingValues => {
+                wfc.with_selector(EntropyQueue::new().with_measure(Measure::RemainingValues))
+            }
+            Selection::Scanline => wfc.with_selector(Scanline::new()),
+            Selection::Random => wfc.with_selector(RandomOrder::new()),
+            Selection::Grow => wfc.with_selector(GrowFromCollapsed::new()),
+        };
+
+        if let Err(err) = generate(&mut wfc, args.renderer) {
+            eprintln!("Attempt {} failed: {}", attempt + 1, err);
+            continue;
         }
+
+        if let Some(path) = &args.output {
+            let format = args.format.unwrap_or(
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("png") => OutputFormat::Png,
+                    _ => OutputFormat::Text,
+                },
+            );
+            if let Err(err) = write_output(&wfc.grid, path, format, args.tile_size) {
+                eprintln!("Error: unable to write {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        }
+        return ExitCode::SUCCESS;
     }
+
+    eprintln!("Error: giving up after {} attempts", args.max_retries + 1);
+    ExitCode::FAILURE
 }
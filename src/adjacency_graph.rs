@@ -1,13 +1,59 @@
-use crate::types::{PossibleValue, PossibleValues, TileType};
+use crate::{
+    direction::Direction,
+    registry::TileRegistry,
+    symmetry::{Orientation, TileVariants},
+    tile_set::TileSet,
+    types::{PossibleValue, PossibleValues, Tile, TileType},
+};
 use std::
     collections::{HashMap, HashSet}
 ;
 
+/// Creates one tile per distinct value of the sample, weighted by how often the
+/// value occurs and named after its `Debug` representation.
+fn tiles_from_example<T: TileType>(sample: &[Vec<T>]) -> HashMap<T, PossibleValue<T>> {
+    let mut counts: HashMap<&T, i32> = HashMap::new();
+    for value in sample.iter().flatten() {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(value, count)| {
+            let tile = Tile::new(value.clone(), &format!("{:?}", value), count);
+            (value.clone(), tile)
+        })
+        .collect()
+}
+
+/// Calls `f` for every pair of neighboring values in the sample, with the
+/// direction of the second value as seen from the first.
+fn for_each_example_neighbor<T: TileType>(
+    sample: &[Vec<T>],
+    mut f: impl FnMut(&T, Direction, &T),
+) {
+    for (x, row) in sample.iter().enumerate() {
+        for (y, value) in row.iter().enumerate() {
+            if let Some(east) = row.get(y + 1) {
+                f(value, Direction::East, east);
+            }
+            if let Some(south) = sample.get(x + 1).and_then(|next_row| next_row.get(y)) {
+                f(value, Direction::South, south);
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AdjacencyGraph<T: TileType> {
     graph: HashMap<PossibleValue<T>, HashSet<PossibleValue<T>>>,
 }
 
+#[allow(
+    clippy::new_without_default,
+    clippy::needless_borrow,
+    clippy::unwrap_or_default,
+    clippy::unnecessary_map_or
+)]
 impl<T: TileType> AdjacencyGraph<T> {
     // add a vec version that takes all the types? idk
     pub fn new() -> Self {
@@ -16,6 +62,16 @@ impl<T: TileType> AdjacencyGraph<T> {
         }
     }
 
+    /// Learns the adjacencies from a hand drawn sample, indexed as
+    /// `sample[x][y]`. Every pair of neighbors in the sample becomes an
+    /// adjacency, and the returned tiles are weighted by their frequency.
+    pub fn from_example(sample: &[Vec<T>]) -> (Self, PossibleValues<T>) {
+        let tiles = tiles_from_example(sample);
+        let mut graph = Self::new();
+        for_each_example_neighbor(sample, |a, _, b| graph.add_adjacency(&tiles[a], &tiles[b]));
+        (graph, tiles.into_values().collect())
+    }
+
     pub fn add_self_adjacencies(&mut self, a: Vec<&PossibleValue<T>>) {
         for tile in a {
             self.add_self_adjacency(&tile);
@@ -50,4 +106,150 @@ impl<T: TileType> AdjacencyGraph<T> {
         // self.graph.get(tile).cloned().unwrap_or_default()
         self.graph.get(tile)
     }
+
+    /// Precompiles the graph into one mask per registered tile, holding the
+    /// indices of its valid neighbors.
+    pub fn compile(&self, registry: &TileRegistry<T>) -> Vec<TileSet> {
+        registry
+            .tiles()
+            .iter()
+            .map(|tile| match self.graph.get(tile) {
+                Some(neighbors) => registry.to_set(neighbors),
+                None => registry.empty_set(),
+            })
+            .collect()
+    }
+}
+
+/// Adjacency graph where the allowed neighbors depend on the side of the tile
+/// they are placed on.
+#[derive(Debug, Clone)]
+pub struct DirectionalAdjacencyGraph<T: TileType> {
+    graph: HashMap<(PossibleValue<T>, Direction), HashSet<PossibleValue<T>>>,
+}
+
+impl<T: TileType> Default for DirectionalAdjacencyGraph<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TileType> DirectionalAdjacencyGraph<T> {
+    pub fn new() -> Self {
+        Self {
+            graph: HashMap::new(),
+        }
+    }
+
+    /// Like [`AdjacencyGraph::from_example`], but keeps the direction in which
+    /// each pair of neighbors was observed.
+    pub fn from_example(sample: &[Vec<T>]) -> (Self, PossibleValues<T>) {
+        let tiles = tiles_from_example(sample);
+        let mut graph = Self::new();
+        for_each_example_neighbor(sample, |a, direction, b| {
+            graph.add_adjacency(&tiles[a], direction, &tiles[b])
+        });
+        (graph, tiles.into_values().collect())
+    }
+
+    /// Allows `b` to be placed in `direction` of `a`. The mirrored adjacency,
+    /// `a` in the opposite direction of `b`, is added as well.
+    pub fn add_adjacency(
+        &mut self,
+        a: &PossibleValue<T>,
+        direction: Direction,
+        b: &PossibleValue<T>,
+    ) {
+        self.graph
+            .entry((a.clone(), direction))
+            .or_default()
+            .insert(b.clone());
+        self.graph
+            .entry((b.clone(), direction.opposite()))
+            .or_default()
+            .insert(a.clone());
+    }
+
+    /// Allows `b` in `direction` of `a`, both in their original orientation,
+    /// and the same pair turned and flipped in every way, with the direction
+    /// turned along.
+    pub fn add_variant_adjacency(
+        &mut self,
+        a: &TileVariants<T>,
+        direction: Direction,
+        b: &TileVariants<T>,
+    ) {
+        for orientation in Orientation::all() {
+            self.add_adjacency(
+                a.get(orientation),
+                orientation.apply(direction),
+                b.get(orientation),
+            );
+        }
+    }
+
+    pub fn add_adjacency_all_directions(&mut self, a: &PossibleValue<T>, b: &PossibleValue<T>) {
+        self.add_adjacency_directions(a, &Direction::ALL, b);
+    }
+
+    /// Adds the adjacency for each of `directions`, e.g. only the horizontal
+    /// [`Direction::SQUARE`] ones of a 3D grid.
+    pub fn add_adjacency_directions(
+        &mut self,
+        a: &PossibleValue<T>,
+        directions: &[Direction],
+        b: &PossibleValue<T>,
+    ) {
+        for &direction in directions {
+            self.add_adjacency(a, direction, b);
+        }
+    }
+
+    pub fn add_self_adjacencies(&mut self, a: Vec<&PossibleValue<T>>) {
+        for tile in a {
+            self.add_self_adjacency(tile);
+        }
+    }
+
+    pub fn add_self_adjacency(&mut self, a: &PossibleValue<T>) {
+        self.add_adjacency_all_directions(a, a);
+    }
+
+    pub fn is_valid_neighbor(
+        &self,
+        a: &PossibleValue<T>,
+        direction: Direction,
+        b: &PossibleValue<T>,
+    ) -> bool {
+        self.graph
+            .get(&(a.clone(), direction))
+            .is_some_and(|neighbors| neighbors.contains(b))
+    }
+
+    pub fn get_valid_neighbors(
+        &self,
+        tile: &PossibleValue<T>,
+        direction: Direction,
+    ) -> Option<&PossibleValues<T>> {
+        self.graph.get(&(tile.clone(), direction))
+    }
+
+    /// Precompiles the graph into one mask per direction and registered tile,
+    /// holding the indices of the valid neighbors on that side.
+    pub fn compile(&self, registry: &TileRegistry<T>) -> HashMap<Direction, Vec<TileSet>> {
+        Direction::ALL
+            .iter()
+            .map(|&direction| {
+                let masks = registry
+                    .tiles()
+                    .iter()
+                    .map(|tile| match self.graph.get(&(tile.clone(), direction)) {
+                        Some(neighbors) => registry.to_set(neighbors),
+                        None => registry.empty_set(),
+                    })
+                    .collect();
+                (direction, masks)
+            })
+            .collect()
+    }
 }
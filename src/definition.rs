@@ -0,0 +1,326 @@
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+use colored::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    adjacency_graph::{AdjacencyGraph, DirectionalAdjacencyGraph},
+    direction::Direction,
+    error::WfcError,
+    rules::{
+        adjacency_rule::AdjacencyRule, directional_adjacency_rule::DirectionalAdjacencyRule, Rule,
+    },
+    traits::{AsciiRenderable, ColorRenderable, Renderer},
+    types::{PossibleValue, PossibleValues, Tile, TileType},
+    wfc::WFC,
+};
+
+/// A colored character, the tile type of tile sets loaded from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsciiTile {
+    pub id: char,
+    pub color: Color,
+}
+
+impl std::hash::Hash for AsciiTile {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl TileType for AsciiTile {}
+
+impl AsciiRenderable for AsciiTile {
+    fn get_ascii_representation(&self) -> char {
+        self.id
+    }
+}
+
+impl ColorRenderable for AsciiTile {
+    fn get_color(&self) -> Color {
+        self.color
+    }
+}
+
+fn default_weight() -> i32 {
+    1
+}
+
+fn default_color() -> String {
+    "white".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileDefinition {
+    /// Unique name, adjacencies and presets refer to the tile by it.
+    pub name: String,
+    /// Character drawn by the ascii renderers.
+    pub id: char,
+    #[serde(default = "default_weight")]
+    pub weight: i32,
+    /// A color name like "blue" or "bright green", or a hex code like
+    /// "#1e90ff".
+    #[serde(default = "default_color")]
+    pub color: String,
+    /// Whether the tile may be placed next to itself.
+    #[serde(default)]
+    pub self_adjacent: bool,
+}
+
+/// Allows `b` next to `a`. Without `directions` the pair fits on every side,
+/// otherwise only with `b` in one of the listed directions of `a`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdjacencyDefinition {
+    pub a: String,
+    pub b: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directions: Option<Vec<Direction>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresetDefinition {
+    pub x: usize,
+    pub y: usize,
+    pub tile: String,
+}
+
+/// Tiles, adjacencies, grid size and presets as stored in a TOML or JSON
+/// file, so tile sets can be changed without recompiling:
+///
+/// ```toml
+/// width = 15
+/// height = 25
+///
+/// [[tiles]]
+/// name = "Water"
+/// id = "~"
+/// weight = 2
+/// color = "blue"
+/// self_adjacent = true
+///
+/// [[tiles]]
+/// name = "Beach"
+/// id = "."
+/// color = "yellow"
+///
+/// [[adjacencies]]
+/// a = "Water"
+/// b = "Beach"
+///
+/// [[presets]]
+/// x = 0
+/// y = 0
+/// tile = "Water"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileSetDefinition {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<TileDefinition>,
+    #[serde(default)]
+    pub adjacencies: Vec<AdjacencyDefinition>,
+    #[serde(default)]
+    pub presets: Vec<PresetDefinition>,
+}
+
+impl TileSetDefinition {
+    /// Reads a definition, the format is picked from the extension of
+    /// `path`: `.toml` or `.json`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WfcError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| {
+            WfcError::InvalidDefinition(format!("{}: {}", path.display(), err))
+        })?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(WfcError::InvalidDefinition(format!(
+                "{}: expected a .toml or .json file",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, WfcError> {
+        toml::from_str(text).map_err(|err| WfcError::InvalidDefinition(err.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, WfcError> {
+        serde_json::from_str(text).map_err(|err| WfcError::InvalidDefinition(err.to_string()))
+    }
+
+    /// Creates the tiles and adjacency graphs. Weights must be positive, and
+    /// adjacencies and presets must only refer to defined tiles.
+    pub fn build(&self) -> Result<LoadedTileSet, WfcError> {
+        let mut tiles = HashMap::new();
+        for definition in &self.tiles {
+            if definition.weight <= 0 {
+                return Err(WfcError::InvalidDefinition(format!(
+                    "weight {} of tile {} isn't positive",
+                    definition.weight, definition.name
+                )));
+            }
+            let color = Color::from_str(&definition.color).map_err(|_| {
+                WfcError::InvalidDefinition(format!(
+                    "unknown color {} of tile {}",
+                    definition.color, definition.name
+                ))
+            })?;
+            let tile = Tile::new(
+                AsciiTile {
+                    id: definition.id,
+                    color,
+                },
+                &definition.name,
+                definition.weight,
+            );
+            if tiles.insert(definition.name.clone(), tile).is_some() {
+                return Err(WfcError::InvalidDefinition(format!(
+                    "tile {} is defined twice",
+                    definition.name
+                )));
+            }
+        }
+        let tile = |name: &String| {
+            tiles
+                .get(name)
+                .ok_or_else(|| WfcError::UnknownTile(name.clone()))
+        };
+
+        let mut adjacency_graph = AdjacencyGraph::new();
+        let mut directional_adjacency_graph = DirectionalAdjacencyGraph::new();
+        for definition in self.tiles.iter().filter(|tile| tile.self_adjacent) {
+            let tile = tile(&definition.name)?;
+            adjacency_graph.add_self_adjacency(tile);
+            directional_adjacency_graph.add_self_adjacency(tile);
+        }
+        for adjacency in &self.adjacencies {
+            let (a, b) = (tile(&adjacency.a)?, tile(&adjacency.b)?);
+            adjacency_graph.add_adjacency(a, b);
+            match &adjacency.directions {
+                Some(directions) => {
+                    directional_adjacency_graph.add_adjacency_directions(a, directions, b)
+                }
+                None => directional_adjacency_graph.add_adjacency_all_directions(a, b),
+            }
+        }
+        let is_directional = self
+            .adjacencies
+            .iter()
+            .any(|adjacency| adjacency.directions.is_some());
+
+        let presets = self
+            .presets
+            .iter()
+            .map(|preset| Ok((tile(&preset.tile)?.clone(), preset.x, preset.y)))
+            .collect::<Result<_, WfcError>>()?;
+
+        Ok(LoadedTileSet {
+            width: self.width,
+            height: self.height,
+            tiles: tiles.into_values().collect(),
+            adjacency_graph,
+            directional_adjacency_graph: is_directional.then_some(directional_adjacency_graph),
+            presets,
+        })
+    }
+}
+
+/// A [`TileSetDefinition`] turned into tiles and adjacency graphs.
+pub struct LoadedTileSet {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: PossibleValues<AsciiTile>,
+    /// Every adjacency, ignoring their directions.
+    pub adjacency_graph: AdjacencyGraph<AsciiTile>,
+    /// Only set if some adjacency is restricted to certain directions.
+    pub directional_adjacency_graph: Option<DirectionalAdjacencyGraph<AsciiTile>>,
+    /// Tiles to place before running, with their (x, y) coordinates.
+    pub presets: Vec<(PossibleValue<AsciiTile>, usize, usize)>,
+}
+
+impl LoadedTileSet {
+    pub fn find_tile(&self, name: &str) -> Option<&PossibleValue<AsciiTile>> {
+        self.tiles.iter().find(|tile| tile.name == name)
+    }
+
+    /// A [`DirectionalAdjacencyRule`] if any adjacency has directions, an
+    /// [`AdjacencyRule`] otherwise.
+    pub fn rules(&self) -> Vec<Box<dyn Rule<AsciiTile>>> {
+        match &self.directional_adjacency_graph {
+            Some(graph) => vec![Box::new(DirectionalAdjacencyRule::new(graph.clone()))],
+            None => vec![Box::new(AdjacencyRule::new(self.adjacency_graph.clone()))],
+        }
+    }
+
+    /// Creates a solver for a grid of the defined size, with the presets
+    /// already placed.
+    pub fn build_wfc<R: Renderer<AsciiTile>>(
+        &self,
+        renderer: Option<R>,
+    ) -> Result<WFC<AsciiTile, R>, WfcError> {
+        let mut wfc = WFC::new(
+            self.width,
+            self.height,
+            self.tiles.clone(),
+            self.rules(),
+            renderer,
+        );
+        for (tile, x, y) in &self.presets {
+            wfc.preset_tile(tile.clone(), *x, *y)?;
+        }
+        Ok(wfc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILES: &str = r#"
+        width = 2
+        height = 2
+
+        [[tiles]]
+        name = "Water"
+        id = "~"
+        self_adjacent = true
+    "#;
+
+    #[test]
+    fn loads_the_example_tile_set() {
+        let loaded = TileSetDefinition::load("tilesets/islands.toml")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(loaded.find_tile("Water").is_some());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let misspelled = TILES.replace("self_adjacent", "self_adjacant");
+        assert!(matches!(
+            TileSetDefinition::from_toml(&misspelled),
+            Err(WfcError::InvalidDefinition(_))
+        ));
+        let extra = format!("depth = 3\n{}", TILES);
+        assert!(TileSetDefinition::from_toml(&extra).is_err());
+    }
+
+    #[test]
+    fn rejects_weights_that_arent_positive() {
+        let mut definition = TileSetDefinition::from_toml(TILES).unwrap();
+        assert!(definition.build().is_ok());
+        for weight in [0, -1] {
+            definition.tiles[0].weight = weight;
+            assert!(matches!(
+                definition.build(),
+                Err(WfcError::InvalidDefinition(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// Direction from a cell towards one of its neighbors.
+///
+/// Directions follow the layout used by the renderers: `x` selects the printed
+/// row and `y` the column, so `North` is `x - 1` and `East` is `y + 1`. Square
+/// lattices use the four cardinal directions, hex lattices east, west and the
+/// four diagonals, cubic lattices the cardinal directions plus `Up` (z + 1)
+/// and `Down`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+    NorthEast,
+    SouthEast,
+    SouthWest,
+    NorthWest,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 10] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::NorthEast,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::NorthWest,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub const SQUARE: [Direction; 4] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+    ];
+
+    pub const HEX: [Direction; 6] = [
+        Direction::NorthEast,
+        Direction::East,
+        Direction::SouthEast,
+        Direction::SouthWest,
+        Direction::West,
+        Direction::NorthWest,
+    ];
+
+    pub const CUBIC: [Direction; 6] = [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::NorthWest => Direction::SouthEast,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+}
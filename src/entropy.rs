@@ -0,0 +1,329 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use rand::Rng;
+
+use crate::{
+    grid::{Cell, Grid},
+    selection::is_candidate,
+    types::TileType,
+};
+
+/// How to choose between cells of equal entropy. Picking the first one in
+/// index order makes the solved area sweep across the grid in a visible
+/// diagonal, the random strategies grow it more organically while staying
+/// reproducible under a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TieBreak {
+    /// The cell with the lowest index.
+    #[default]
+    Deterministic,
+    /// A random cell among those with exactly the lowest entropy.
+    RandomAmongTies,
+    /// Adds random noise between 0 and the given amount to every entropy.
+    /// A tiny amount like `1e-6` only breaks ties, larger ones also mix up
+    /// cells of nearly the same entropy.
+    Noise(f64),
+}
+
+/// What [`EntropyQueue`] orders the cells by, lowest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Measure {
+    /// Shannon entropy of the weights of the remaining tiles.
+    #[default]
+    ShannonEntropy,
+    /// Number of remaining tiles, ignoring their weights. Also known as the
+    /// minimum remaining values heuristic.
+    RemainingValues,
+}
+
+impl Measure {
+    fn of<T: TileType>(&self, cell: &Cell<T>) -> f64 {
+        match self {
+            Measure::ShannonEntropy => cell.entropy(),
+            Measure::RemainingValues => cell.possible_count() as f64,
+        }
+    }
+}
+
+/// Entropy of a cell at the time it was queued.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    entropy: f64,
+    /// The entropy with noise added, what the heap is ordered by.
+    key: f64,
+    /// Random among ties, 0 for deterministic ordering.
+    tie: u64,
+    cell: usize,
+}
+
+impl Entry {
+    fn new<R: Rng + ?Sized>(entropy: f64, cell: usize, tie_break: TieBreak, rng: &mut R) -> Self {
+        let (key, tie) = match tie_break {
+            TieBreak::Deterministic => (entropy, 0),
+            TieBreak::RandomAmongTies => (entropy, rng.random()),
+            TieBreak::Noise(amount) => (entropy + rng.random::<f64>() * amount, 0),
+        };
+        Self {
+            entropy,
+            key,
+            tie,
+            cell,
+        }
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // Reversed, so the max-heap pops the lowest entropy first and the lowest
+    // index among equal entropies
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .key
+            .total_cmp(&self.key)
+            .then_with(|| other.tie.cmp(&self.tie))
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
+
+/// Min-heap of the cells that still have to be observed, ordered by their
+/// Shannon entropy or another [`Measure`].
+///
+/// Entries are invalidated lazily: a cell whose entropy changed is queued
+/// again with [`EntropyQueue::update`], and outdated entries are skipped or
+/// requeued when they reach the top.
+#[derive(Debug, Clone)]
+pub struct EntropyQueue {
+    heap: BinaryHeap<Entry>,
+    stale: bool,
+    tie_break: TieBreak,
+    measure: Measure,
+}
+
+impl Default for EntropyQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntropyQueue {
+    /// An empty queue ordering cells by their Shannon entropy, filled from the
+    /// grid on the first call to [`EntropyQueue::lowest`].
+    pub fn new() -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            stale: true,
+            tie_break: TieBreak::default(),
+            measure: Measure::default(),
+        }
+    }
+
+    /// Changes how cells of equal entropy are ordered, the queue is rebuilt.
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self.invalidate();
+        self
+    }
+
+    /// Orders the cells by `measure` instead of their Shannon entropy, the
+    /// queue is rebuilt.
+    pub fn with_measure(mut self, measure: Measure) -> Self {
+        self.measure = measure;
+        self.invalidate();
+        self
+    }
+
+    /// Drops every entry, e.g. after the grid was replaced. The queue is
+    /// rebuilt from the grid when it is next used.
+    pub fn invalidate(&mut self) {
+        self.heap.clear();
+        self.stale = true;
+    }
+
+    /// Queues `cell` with its current entropy, call whenever its possible
+    /// values change.
+    pub fn update<T: TileType, R: Rng + ?Sized>(
+        &mut self,
+        grid: &Grid<T>,
+        cell: usize,
+        rng: &mut R,
+    ) {
+        if !self.stale && is_candidate(grid, cell) {
+            let entropy = self.measure.of(grid.cell(cell).unwrap());
+            self.heap
+                .push(Entry::new(entropy, cell, self.tie_break, rng));
+        }
+    }
+
+    fn rebuild<T: TileType, R: Rng + ?Sized>(&mut self, grid: &Grid<T>, rng: &mut R) {
+        self.heap = (0..grid.len())
+            .filter(|&cell| is_candidate(grid, cell))
+            .map(|cell| {
+                let entropy = self.measure.of(grid.cell(cell).unwrap());
+                Entry::new(entropy, cell, self.tie_break, rng)
+            })
+            .collect();
+        self.stale = false;
+    }
+
+    /// The uncollapsed active cell with the lowest entropy, `None` once every
+    /// cell is collapsed. The cell stays queued until it is collapsed.
+    pub fn lowest<T: TileType, R: Rng + ?Sized>(
+        &mut self,
+        grid: &Grid<T>,
+        rng: &mut R,
+    ) -> Option<usize> {
+        if self.stale {
+            self.rebuild(grid, rng);
+        }
+        if let Some(cell) = self.peek_valid(grid, rng) {
+            return Some(cell);
+        }
+        // Cells changed directly on the grid may not have been queued, look
+        // at all of them once more before giving up
+        self.rebuild(grid, rng);
+        self.peek_valid(grid, rng)
+    }
+
+    fn peek_valid<T: TileType, R: Rng + ?Sized>(
+        &mut self,
+        grid: &Grid<T>,
+        rng: &mut R,
+    ) -> Option<usize> {
+        while let Some(&entry) = self.heap.peek() {
+            if !is_candidate(grid, entry.cell) {
+                self.heap.pop();
+                continue;
+            }
+            let entropy = self.measure.of(grid.cell(entry.cell).unwrap());
+            if entropy != entry.entropy {
+                // Changed without being requeued
+                self.heap.pop();
+                self.heap
+                    .push(Entry::new(entropy, entry.cell, self.tie_break, rng));
+                continue;
+            }
+            return Some(entry.cell);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::types::{PossibleValue, Tile};
+
+    /// A row of `len` cells that may each be red, green or blue.
+    fn colors(len: usize) -> (Grid<char>, Vec<PossibleValue<char>>) {
+        let tiles: Vec<_> = [('R', "Red"), ('G', "Green"), ('B', "Blue")]
+            .map(|(id, name)| Tile::new(id, name, 1))
+            .into();
+        let mut grid = Grid::new(1, len, tiles.iter().cloned().collect());
+        grid.set_journaling(true);
+        (grid, tiles)
+    }
+
+    #[test]
+    fn picks_the_cell_with_the_lowest_entropy() {
+        let (mut grid, tiles) = colors(4);
+        let (mut queue, mut rng) = (EntropyQueue::new(), StdRng::seed_from_u64(0));
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(0));
+
+        grid.cell_mut(2).unwrap().ban(&tiles[0]);
+        queue.update(&grid, 2, &mut rng);
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(2));
+    }
+
+    #[test]
+    fn requeues_cells_whose_entropy_changed_unnoticed() {
+        let (mut grid, tiles) = colors(4);
+        let (mut queue, mut rng) = (EntropyQueue::new(), StdRng::seed_from_u64(0));
+        queue.lowest(&grid, &mut rng);
+        grid.cell_mut(1).unwrap().ban(&tiles[0]);
+        queue.update(&grid, 1, &mut rng);
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(1));
+
+        // Restored without an update, its entry is outdated
+        grid.undo(0);
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(0));
+    }
+
+    #[test]
+    fn skips_collapsed_cells() {
+        let (mut grid, tiles) = colors(3);
+        let (mut queue, mut rng) = (EntropyQueue::new(), StdRng::seed_from_u64(0));
+        queue.lowest(&grid, &mut rng);
+        grid.cell_mut(0).unwrap().set(&tiles[0]).unwrap();
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(1));
+    }
+
+    #[test]
+    fn rebuilds_once_the_queue_ran_dry() {
+        let (mut grid, tiles) = colors(3);
+        let (mut queue, mut rng) = (EntropyQueue::new(), StdRng::seed_from_u64(0));
+        queue.lowest(&grid, &mut rng);
+        for cell in 0..3 {
+            grid.cell_mut(cell).unwrap().set(&tiles[1]).unwrap();
+        }
+        assert_eq!(queue.lowest(&grid, &mut rng), None);
+
+        // Reopened without updates, only a rebuild finds them
+        grid.undo(1);
+        assert_eq!(queue.lowest(&grid, &mut rng), Some(1));
+    }
+
+    /// The cells picked first with `tie_break` under seeds 0 to 19, from a
+    /// row where cells 1, 3 and 4 have one tile less than the others.
+    fn first_picks(tie_break: TieBreak) -> Vec<usize> {
+        let (mut grid, tiles) = colors(6);
+        for cell in [1, 3, 4] {
+            grid.cell_mut(cell).unwrap().ban(&tiles[2]);
+        }
+        (0..20)
+            .map(|seed| {
+                let mut queue = EntropyQueue::new().with_tie_break(tie_break);
+                queue
+                    .lowest(&grid, &mut StdRng::seed_from_u64(seed))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_ties_go_to_the_lowest_index() {
+        assert!(first_picks(TieBreak::Deterministic)
+            .iter()
+            .all(|&cell| cell == 1));
+    }
+
+    #[test]
+    fn random_ties_stay_among_the_lowest_entropy() {
+        for tie_break in [TieBreak::RandomAmongTies, TieBreak::Noise(1e-6)] {
+            let picks = first_picks(tie_break);
+            assert!(picks.iter().all(|cell| [1, 3, 4].contains(cell)));
+            assert!(picks.iter().any(|&cell| cell != 1), "{:?}", tie_break);
+            assert_eq!(picks, first_picks(tie_break));
+        }
+    }
+
+    #[test]
+    fn large_noise_mixes_up_nearly_equal_cells() {
+        let picks = first_picks(TieBreak::Noise(10.0));
+        assert!(picks.iter().any(|cell| ![1, 3, 4].contains(cell)));
+    }
+}
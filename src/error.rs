@@ -0,0 +1,69 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WfcError {
+    /// The coordinates are outside of the grid.
+    OutOfBounds { x: usize, y: usize, z: usize },
+    /// No cell has this index.
+    UnknownCell { cell: usize },
+    /// The cell was already collapsed and cannot be observed again.
+    AlreadyCollapsed { cell: usize },
+    /// `rule` removed the last possible value of the cell.
+    Contradiction { cell: usize, rule: String },
+    /// `rule` can't be satisfied by any cell anymore, e.g. too few cells may
+    /// still become a tile. Backtracked like a contradiction.
+    Unsatisfiable { rule: String },
+    /// The tile is not part of the tiles the grid was created with.
+    UnknownTile(String),
+    /// The remaining tiles of a cell can't be picked from, e.g. all of their
+    /// weights are zero or negative.
+    InvalidWeights(String),
+    /// A sample can't be used to learn tiles or patterns from.
+    InvalidSample(String),
+    /// A tile set definition can't be read or refers to something that
+    /// doesn't exist.
+    InvalidDefinition(String),
+    /// A saved grid can't be read or doesn't fit the grid or tiles it is
+    /// loaded into.
+    InvalidSnapshot(String),
+    /// The renderer couldn't output the grid, e.g. an image file couldn't be
+    /// written.
+    RenderFailed(String),
+    /// A contradiction could not be resolved within the backtrack depth, or
+    /// the budget of `max_backtracks` ran out.
+    BacktrackFailed(Box<WfcError>),
+}
+
+impl fmt::Display for WfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::OutOfBounds { x, y, z } => {
+                write!(f, "Cell at ({}, {}, {}) does not exist", x, y, z)
+            }
+            WfcError::UnknownCell { cell } => write!(f, "Cell {} does not exist", cell),
+            WfcError::AlreadyCollapsed { cell } => write!(f, "Cell {} is already collapsed", cell),
+            WfcError::Contradiction { cell, rule } => {
+                write!(f, "Contradiction at cell {} caused by {}", cell, rule)
+            }
+            WfcError::Unsatisfiable { rule } => write!(f, "{} can't be satisfied", rule),
+            WfcError::UnknownTile(name) => write!(f, "Unknown tile {}", name),
+            WfcError::InvalidWeights(reason) => write!(f, "Invalid tile weights: {}", reason),
+            WfcError::InvalidSample(reason) => write!(f, "Invalid sample: {}", reason),
+            WfcError::InvalidDefinition(reason) => {
+                write!(f, "Invalid tile set definition: {}", reason)
+            }
+            WfcError::InvalidSnapshot(reason) => write!(f, "Invalid grid snapshot: {}", reason),
+            WfcError::RenderFailed(reason) => write!(f, "Unable to render: {}", reason),
+            WfcError::BacktrackFailed(cause) => write!(f, "Unable to backtrack: {}", cause),
+        }
+    }
+}
+
+impl Error for WfcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WfcError::BacktrackFailed(cause) => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -1,136 +1,513 @@
-use std::collections::HashSet;
+use std::{fmt, sync::Arc};
 
-use rand::{rng, seq::IndexedRandom};
+use rand::{seq::IndexedRandom, Rng};
 
-use crate::types::{PossibleValue, PossibleValues, TileType};
+use crate::{
+    direction::Direction,
+    error::WfcError,
+    registry::TileRegistry,
+    tile_set::TileSet,
+    topology::{Lattice, LatticeTopology, MaskedTopology, Topology},
+    types::{PossibleValue, PossibleValues, TileType},
+};
 
+/// What happens to the inactive cells of a masked grid, see
+/// [`Grid::apply_mask`].
+#[derive(Debug, Clone)]
+pub enum InactiveCells<T: TileType> {
+    /// Inactive cells are no one's neighbor, active cells next to them are
+    /// unconstrained on that side.
+    Ignored,
+    /// Inactive cells hold this tile, so rules constrain their active
+    /// neighbors as if the tile had been placed there.
+    Void(PossibleValue<T>),
+    /// Like [`InactiveCells::Void`], and the tile is also removed from every
+    /// active cell, so it only occurs outside the shape.
+    ExclusiveVoid(PossibleValue<T>),
+}
+
+/// Cells are addressed by index. Which cells are neighbors is up to the
+/// grid's [`Topology`]; on lattices cells are laid out layer by layer and row
+/// by row, see [`Grid::index`] and [`Grid::coordinates`].
 #[derive(Clone, Debug)]
 pub struct Grid<T: TileType> {
-    pub width: usize,
-    pub height: usize,
-    cells: Vec<Vec<Cell<T>>>,
+    topology: Arc<dyn Topology>,
+    /// `None` while every cell is active, see [`Grid::apply_mask`].
+    active: Option<Arc<[bool]>>,
+    /// Whether inactive cells are hidden from their neighbors.
+    hide_inactive: bool,
+    /// Previous state of the cells changed through [`Grid::cell_mut`], only
+    /// recorded while journaling, see [`Grid::undo`].
+    journal: Option<Vec<(usize, Cell<T>)>>,
+    registry: Arc<TileRegistry<T>>,
+    cells: Vec<Cell<T>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Cell<T: TileType> {
-    pub possible_values: PossibleValues<T>,
+    registry: Arc<TileRegistry<T>>,
+    domain: TileSet,
+    // Sums of `w` and `w * log2(w)` over the weights of the domain, updated
+    // as tiles are removed so the entropy doesn't need to be recomputed
+    weight_sum: f64,
+    weight_log_weight_sum: f64,
+}
+
+/// Terms tile `index` adds to the entropy sums of a cell. Tiles without a
+/// positive weight can't be picked and add nothing.
+fn entropy_terms<T: TileType>(registry: &TileRegistry<T>, index: usize) -> (f64, f64) {
+    let weight = registry.get(index).unwrap().weight as f64;
+    if weight > 0.0 {
+        (weight, weight * weight.log2())
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+impl<T: TileType> fmt::Debug for Cell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cell")
+            .field("possible_values", &self.possible_values().collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl<T: TileType> Cell<T> {
-    pub fn new(possible_values: PossibleValues<T>) -> Self {
-        Self { possible_values }
+    pub fn new(registry: Arc<TileRegistry<T>>, domain: TileSet) -> Self {
+        let mut cell = Self {
+            registry,
+            domain,
+            weight_sum: 0.0,
+            weight_log_weight_sum: 0.0,
+        };
+        cell.update_entropy();
+        cell
+    }
+
+    fn update_entropy(&mut self) {
+        (self.weight_sum, self.weight_log_weight_sum) = (0.0, 0.0);
+        for index in self.domain.iter() {
+            let (weight, weight_log_weight) = entropy_terms(&self.registry, index);
+            self.weight_sum += weight;
+            self.weight_log_weight_sum += weight_log_weight;
+        }
+    }
+
+    fn forget_entropy_terms(&mut self, index: usize) {
+        let (weight, weight_log_weight) = entropy_terms(&self.registry, index);
+        self.weight_sum -= weight;
+        self.weight_log_weight_sum -= weight_log_weight;
+    }
+
+    /// Shannon entropy of the weights of the remaining tiles, in bits.
+    pub fn entropy(&self) -> f64 {
+        if self.weight_sum <= 0.0 {
+            return 0.0; // Prevent log(0) errors
+        }
+        // Rounding errors of the running sums could make it slightly negative
+        (self.weight_sum.log2() - self.weight_log_weight_sum / self.weight_sum).max(0.0)
+    }
+
+    /// Indices into the registry of the tiles this cell may still become.
+    pub fn domain(&self) -> &TileSet {
+        &self.domain
+    }
+
+    pub fn possible_values(&self) -> impl Iterator<Item = &PossibleValue<T>> + '_ {
+        self.domain
+            .iter()
+            .map(|index| self.registry.get(index).unwrap())
+    }
+
+    pub fn possible_count(&self) -> usize {
+        self.domain.len()
     }
 
     pub fn is_collapsed(&self) -> bool {
-        self.possible_values.len() == 1
+        self.domain.single().is_some()
     }
 
     pub fn get_collapsed_value(&self) -> Option<PossibleValue<T>> {
-        // println!("possible_values: {:?}", self.possible_values);
-        if self.is_collapsed() {
-            // TODO should we return a clone? It's an arc, so it should be ok to clone?
-            // Some(self.possible_values.iter().cloned().next())
-            return Some(self.possible_values.iter().next().unwrap().clone());
-        }
-        None
+        self.domain
+            .single()
+            .map(|index| self.registry.get(index).unwrap().clone())
+    }
+
+    /// A cell with no possible values left can never be collapsed; the
+    /// solver treats this as a contradiction and backtracks.
+    pub fn is_contradiction(&self) -> bool {
+        self.domain.is_empty()
     }
 
     pub fn constrain_by_name(&mut self, allowed: &str) -> bool {
-        let initial_len = self.possible_values.len();
-        self.possible_values.retain(|tile| &tile.name != allowed);
-        assert!(self.possible_values.len() != 0);
-        return initial_len != self.possible_values.len();
+        self.constrain_by_names(vec![allowed])
     }
 
     pub fn constrain_by_names(&mut self, allowed: Vec<&str>) -> bool {
-        let initial_len = self.possible_values.len();
-        self.possible_values
-            .retain(|tile| !allowed.contains(&&tile.name[..]));
-        assert!(self.possible_values.len() != 0);
-        return initial_len != self.possible_values.len();
+        let mut changed = false;
+        let registry = self.registry.clone();
+        for (index, tile) in registry.tiles().iter().enumerate() {
+            if allowed.contains(&&tile.name[..]) && self.domain.remove(index) {
+                self.forget_entropy_terms(index);
+                changed = true;
+            }
+        }
+        changed
     }
 
-    pub fn constrain(&mut self, allowed: &PossibleValues<T>) -> bool {
-        let initial_len = self.possible_values.len();
-        self.possible_values.retain(|tile| allowed.contains(tile));
-        assert!(self.possible_values.len() != 0);
-        return initial_len != self.possible_values.len();
+    /// Keeps only the tiles in `allowed`, returns true if any were removed.
+    pub fn constrain(&mut self, allowed: &TileSet) -> bool {
+        let Self {
+            registry,
+            domain,
+            weight_sum,
+            weight_log_weight_sum,
+        } = self;
+        domain.intersect_with_each_removed(allowed, |index| {
+            let (weight, weight_log_weight) = entropy_terms(registry, index);
+            *weight_sum -= weight;
+            *weight_log_weight_sum -= weight_log_weight;
+        })
     }
 
-    pub fn collapse(&mut self) -> Result<PossibleValue<T>, String> {
-        if self.is_collapsed() {
-            return Err("Cell is already collapsed".to_string());
+    /// Removes a single value from the cell, returns true if it was present.
+    pub fn ban(&mut self, value: &PossibleValue<T>) -> bool {
+        let Some(index) = self.registry.index_of(value) else {
+            return false;
+        };
+        let removed = self.domain.remove(index);
+        if removed {
+            self.forget_entropy_terms(index);
         }
-        let mut rng = rng();
+        removed
+    }
+
+    /// Forces the cell to `value`, whether or not it was still possible.
+    pub fn set(&mut self, value: &PossibleValue<T>) -> Result<(), WfcError> {
+        let index = self
+            .registry
+            .index_of(value)
+            .ok_or_else(|| WfcError::UnknownTile(value.name.clone()))?;
+        self.domain.clear();
+        self.domain.insert(index);
+        self.update_entropy();
+        Ok(())
+    }
+
+    pub fn collapse<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Result<PossibleValue<T>, WfcError> {
         // println!(
         //     "Collapsing cell with possible_values: {:?}",
         //     self.possible_values
         // );
-        match self
-            .possible_values
-            .iter()
-            .cloned()
-            .collect::<Vec<_>>()
-            .choose_weighted(&mut rng, |tile| tile.weight)
+        let candidates = self.domain.iter().collect::<Vec<_>>();
+        match candidates
+            .choose_weighted(rng, |&index| self.registry.get(index).unwrap().weight)
             .to_owned()
         {
-            Ok(chosen_tile) => {
+            Ok(&chosen_index) => {
                 // println!("Chosen tile: {:?}", chosen_tile);
-                self.possible_values = HashSet::from([chosen_tile.clone()]);
+                self.domain.clear();
+                self.domain.insert(chosen_index);
+                self.update_entropy();
                 // println!("Collapsing to {:?}", chosen_tile);
-                return Ok(chosen_tile.clone());
-            }
-            Err(err) => {
-                return Err(err.to_string());
+                Ok(self.registry.get(chosen_index).unwrap().clone())
             }
+            Err(err) => Err(WfcError::InvalidWeights(err.to_string())),
         }
     }
 }
 
 impl<T: TileType> Grid<T> {
     pub fn new(width: usize, height: usize, possible_tiles: PossibleValues<T>) -> Self {
-        // TODO share identical possible_values between cells?
-        let cells = vec![vec![Cell::new(possible_tiles); height]; width];
+        Self::new_3d(width, height, 1, possible_tiles).with_lattice(Lattice::Square)
+    }
+
+    /// Creates a `width` × `height` × `depth` voxel grid on a cubic lattice.
+    pub fn new_3d(
+        width: usize,
+        height: usize,
+        depth: usize,
+        possible_tiles: PossibleValues<T>,
+    ) -> Self {
+        let topology = LatticeTopology::new(Lattice::Cubic, width, height, depth);
+        Self::from_topology(topology, possible_tiles)
+    }
+
+    /// Creates one cell per cell of `topology`, e.g. a [`GraphTopology`].
+    ///
+    /// [`GraphTopology`]: crate::topology::GraphTopology
+    pub fn from_topology(
+        topology: impl Topology + 'static,
+        possible_tiles: PossibleValues<T>,
+    ) -> Self {
+        let registry = Arc::new(TileRegistry::new(&possible_tiles));
+        let cell = Cell::new(registry.clone(), registry.full_set());
+        let cells = vec![cell; topology.cell_count()];
         Self {
-            width,
-            height,
+            topology: Arc::new(topology),
+            active: None,
+            hide_inactive: false,
+            journal: None,
+            registry,
             cells,
         }
     }
 
-    pub fn get_cells(&self) -> &Vec<Vec<Cell<T>>> {
+    pub fn registry(&self) -> &Arc<TileRegistry<T>> {
+        &self.registry
+    }
+
+    pub fn topology(&self) -> &dyn Topology {
+        self.topology.as_ref()
+    }
+
+    /// The lattice the grid is laid out on, `None` for other topologies.
+    pub fn lattice(&self) -> Option<&LatticeTopology> {
+        self.topology.as_lattice()
+    }
+
+    fn map_lattice(mut self, f: impl FnOnce(&mut LatticeTopology)) -> Self {
+        if let Some(lattice) = self.lattice() {
+            let mut lattice = *lattice;
+            f(&mut lattice);
+            self.topology = Arc::new(lattice);
+            self.hide_inactive_cells();
+        }
+        self
+    }
+
+    fn hide_inactive_cells(&mut self) {
+        if let (true, Some(active)) = (self.hide_inactive, &self.active) {
+            let masked = MaskedTopology::new(self.topology.clone(), active.clone());
+            self.topology = Arc::new(masked);
+        }
+    }
+
+    /// Changes the lattice of the grid, does nothing for other topologies.
+    pub fn with_lattice(self, lattice: Lattice) -> Self {
+        self.map_lattice(|topology| topology.lattice = lattice)
+    }
+
+    /// Turns the grid into a torus along the given axes, so generated maps
+    /// tile seamlessly. Hex lattices only wrap along x with an even width, see
+    /// [`LatticeTopology::wraps_x`]. Does nothing for topologies other than
+    /// lattices.
+    pub fn with_wrapping(self, wrap_x: bool, wrap_y: bool) -> Self {
+        self.map_lattice(|topology| {
+            topology.wrap_x = wrap_x;
+            topology.wrap_y = wrap_y;
+        })
+    }
+
+    /// Number of rows. Grids that are not lattices are treated as a single
+    /// row holding every cell.
+    pub fn width(&self) -> usize {
+        self.lattice().map_or(1, |lattice| lattice.width)
+    }
+
+    /// Number of cells per row.
+    pub fn height(&self) -> usize {
+        self.lattice().map_or(self.len(), |lattice| lattice.height)
+    }
+
+    /// Number of layers along z, 1 for 2D grids.
+    pub fn depth(&self) -> usize {
+        self.lattice().map_or(1, |lattice| lattice.depth)
+    }
+
+    /// Marks the cells for which `is_active(x, y, z)` is false as inactive:
+    /// they are never observed nor rendered. With [`InactiveCells::Ignored`]
+    /// they are no one's neighbor, with [`InactiveCells::Void`] they are
+    /// collapsed to the void tile. Changes are journaled like any other, so
+    /// a mask applied during a run can be backtracked.
+    ///
+    /// Masks accumulate: cells stay inactive when another mask is applied.
+    ///
+    /// Returns the cells that were narrowed, their constraints still need to
+    /// be propagated, see [`WFC::mask_cells`].
+    ///
+    /// [`WFC::mask_cells`]: crate::wfc::WFC::mask_cells
+    pub fn apply_mask(
+        &mut self,
+        is_active: impl Fn(usize, usize, usize) -> bool,
+        mode: &InactiveCells<T>,
+    ) -> Result<Vec<usize>, WfcError> {
+        let active: Arc<[bool]> = (0..self.len())
+            .map(|index| {
+                let (x, y, z) = self.coordinates(index);
+                self.is_active(index) && is_active(x, y, z)
+            })
+            .collect();
+        let mut changed = Vec::new();
+        let (void, exclusive) = match mode {
+            InactiveCells::Ignored => (None, false),
+            InactiveCells::Void(tile) => (Some(tile), false),
+            InactiveCells::ExclusiveVoid(tile) => (Some(tile), true),
+        };
+        if let Some(tile) = void {
+            for index in 0..self.len() {
+                let holds_tile = self.cells[index]
+                    .possible_values()
+                    .any(|value| value == tile);
+                if !active[index] {
+                    self.cell_mut(index).unwrap().set(tile)?;
+                    changed.push(index);
+                } else if exclusive && holds_tile {
+                    self.cell_mut(index).unwrap().ban(tile);
+                    changed.push(index);
+                }
+            }
+        }
+        self.active = Some(active);
+        self.hide_inactive = matches!(mode, InactiveCells::Ignored);
+        self.hide_inactive_cells();
+        Ok(changed)
+    }
+
+    /// Whether inactive cells are hidden from their neighbors, see
+    /// [`InactiveCells::Ignored`].
+    pub(crate) fn hides_inactive(&self) -> bool {
+        self.hide_inactive
+    }
+
+    /// Restores a mask without touching the cells, e.g. one whose void tiles
+    /// are already in place. Like [`Grid::apply_mask`], masks accumulate.
+    pub(crate) fn restore_mask(&mut self, active: &[bool], hide_inactive: bool) {
+        let active: Arc<[bool]> = (0..self.len())
+            .map(|index| self.is_active(index) && active.get(index).copied().unwrap_or(true))
+            .collect();
+        self.active = Some(active);
+        self.hide_inactive |= hide_inactive;
+        self.hide_inactive_cells();
+    }
+
+    /// Whether the cell at `index` takes part in generation, see
+    /// [`Grid::apply_mask`].
+    pub fn is_active(&self, index: usize) -> bool {
+        self.active
+            .as_ref()
+            .is_none_or(|active| active.get(index).copied().unwrap_or(true))
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Index of the cell at (x, y) in the first layer.
+    pub fn index(&self, x: usize, y: usize) -> Option<usize> {
+        self.index_3d(x, y, 0)
+    }
+
+    pub fn index_3d(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        if x < self.width() && y < self.height() && z < self.depth() {
+            Some((z * self.width() + x) * self.height() + y)
+        } else {
+            None
+        }
+    }
+
+    /// (x, y, z) coordinates of the cell at `index`.
+    pub fn coordinates(&self, index: usize) -> (usize, usize, usize) {
+        let height = self.height().max(1);
+        let (layer, y) = (index / height, index % height);
+        (layer % self.width(), y, layer / self.width())
+    }
+
+    pub fn cells(&self) -> &[Cell<T>] {
         &self.cells
     }
 
-    pub fn get_cell_mut(&mut self, x: usize, y: usize) -> Option<&mut Cell<T>> {
-        self.cells.get_mut(x).and_then(|row| row.get_mut(y))
+    /// Rows of layer `z`, each holding `height` cells.
+    pub fn rows(&self, z: usize) -> impl Iterator<Item = &[Cell<T>]> {
+        let layer_len = self.width() * self.height();
+        self.cells[z * layer_len..(z + 1) * layer_len].chunks(self.height().max(1))
     }
 
-    pub fn get_cell(&self, x: usize, y: usize) -> Option<&Cell<T>> {
-        self.cells.get(x).and_then(|row| row.get(y))
+    pub fn cell(&self, index: usize) -> Option<&Cell<T>> {
+        self.cells.get(index)
     }
 
-    pub fn get_valid_coordinates(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
-        let mut valid_coordinates = vec![];
-        if x > 0 {
-            valid_coordinates.push((x - 1, y));
+    pub fn cell_mut(&mut self, index: usize) -> Option<&mut Cell<T>> {
+        let cell = self.cells.get_mut(index)?;
+        if let Some(journal) = &mut self.journal {
+            journal.push((index, cell.clone()));
         }
-        if x < self.width - 1 {
-            valid_coordinates.push((x + 1, y));
+        Some(cell)
+    }
+
+    /// Starts or stops recording the changes made through
+    /// [`Grid::cell_mut`], so they can be undone. Much cheaper than cloning
+    /// the whole grid before every change.
+    pub(crate) fn set_journaling(&mut self, enabled: bool) {
+        if !enabled {
+            self.journal = None;
+        } else if self.journal.is_none() {
+            self.journal = Some(Vec::new());
         }
-        if y > 0 {
-            valid_coordinates.push((x, y - 1));
+    }
+
+    /// Number of recorded changes, a mark to undo to later.
+    pub(crate) fn journal_len(&self) -> usize {
+        self.journal.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Reverts the changes recorded after `mark` and returns the cells that
+    /// were restored.
+    pub(crate) fn undo(&mut self, mark: usize) -> Vec<usize> {
+        let mut restored = Vec::new();
+        if let Some(journal) = &mut self.journal {
+            while journal.len() > mark {
+                let (index, cell) = journal.pop().unwrap();
+                self.cells[index] = cell;
+                restored.push(index);
+            }
         }
-        if y < self.height - 1 {
-            valid_coordinates.push((x, y + 1));
+        restored
+    }
+
+    /// Drops the first `count` recorded changes, they can't be undone
+    /// anymore.
+    pub(crate) fn forget_journal(&mut self, count: usize) {
+        if let Some(journal) = &mut self.journal {
+            journal.drain(..count.min(journal.len()));
         }
-        valid_coordinates
     }
 
-    pub fn get_adjacent_cells(&self, x: usize, y: usize) -> Vec<&Cell<T>> {
+    pub fn get_cell_mut(&mut self, x: usize, y: usize) -> Option<&mut Cell<T>> {
+        let index = self.index(x, y)?;
+        self.cell_mut(index)
+    }
+
+    pub fn get_cell(&self, x: usize, y: usize) -> Option<&Cell<T>> {
+        self.cell(self.index(x, y)?)
+    }
+
+    pub fn get_neighbor(&self, index: usize, direction: Direction) -> Option<usize> {
+        self.get_directional_neighbors(index)
+            .into_iter()
+            .find(|&(d, _)| d == direction)
+            .map(|(_, neighbor)| neighbor)
+    }
+
+    pub fn get_directional_neighbors(&self, index: usize) -> Vec<(Direction, usize)> {
+        self.topology.neighbors(index)
+    }
+
+    pub fn get_neighbors(&self, index: usize) -> Vec<usize> {
+        self.get_directional_neighbors(index)
+            .into_iter()
+            .map(|(_, neighbor)| neighbor)
+            .collect()
+    }
+
+    pub fn get_adjacent_cells(&self, index: usize) -> Vec<&Cell<T>> {
         let mut cells = vec![];
-        for (nx, ny) in self.get_valid_coordinates(x, y) {
-            if let Some(cell) = self.get_cell(nx, ny) {
+        for neighbor in self.get_neighbors(index) {
+            if let Some(cell) = self.cell(neighbor) {
                 cells.push(cell);
             }
         }
@@ -138,42 +515,35 @@ impl<T: TileType> Grid<T> {
         cells
     }
 
-    pub fn collapse_cell(&mut self, x: usize, y: usize) -> Result<PossibleValue<T>, String> {
-        match self.get_cell_mut(x, y) {
+    pub fn collapse_cell<R: Rng + ?Sized>(
+        &mut self,
+        index: usize,
+        rng: &mut R,
+    ) -> Result<PossibleValue<T>, WfcError> {
+        match self.cell_mut(index) {
             Some(cell) => {
                 if cell.is_collapsed() {
-                    return Err(format!("Cell at ({}, {}) is already collapsed", x, y));
+                    return Err(WfcError::AlreadyCollapsed { cell: index });
                 }
 
-                match cell.collapse() {
-                    Ok(possible_value) => {
-                        return Ok(possible_value);
-                    }
-                    Err(err) => {
-                        return Err(err);
-                    }
-                }
-            }
-            None => {
-                return Err(format!("Cell at ({}, {}) does not exist", x, y));
+                cell.collapse(rng)
             }
+            None => Err(WfcError::UnknownCell { cell: index }),
         }
     }
 
     pub fn debug_check_shared_cells(&self) {
-        if self.cells.is_empty() || self.cells[0].is_empty() {
+        if self.cells.is_empty() {
             println!("Grid is empty.");
             return;
         }
 
-        let first_cell = &self.cells[0][0];
+        let first_cell = &self.cells[0];
 
         let mut all_same = true;
-        for (_, row) in self.cells.iter().enumerate() {
-            for (_, cell) in row.iter().enumerate() {
-                if !std::ptr::eq(first_cell, cell) {
-                    all_same = false;
-                }
+        for cell in self.cells.iter() {
+            if !std::ptr::eq(first_cell, cell) {
+                all_same = false;
             }
         }
 
@@ -185,31 +555,121 @@ impl<T: TileType> Grid<T> {
     }
 
     pub fn debug_check_shared_possible_values(&self) {
-        if self.cells.is_empty() || self.cells[0].is_empty() {
+        if self.cells.is_empty() {
             println!("Grid is empty.");
             return;
         }
 
-        let first_possible_values = &self.cells[0][0].possible_values;
+        let first_domain = &self.cells[0].domain;
         let mut all_same = true;
 
-        for (x, row) in self.cells.iter().enumerate() {
-            for (y, cell) in row.iter().enumerate() {
-                if std::ptr::eq(first_possible_values, &cell.possible_values) {
-                    println!(
-                        "Cell ({}, {}) shares the same possible_values reference!",
-                        x, y
-                    );
-                } else {
-                    all_same = false;
-                }
+        for (index, cell) in self.cells.iter().enumerate() {
+            if std::ptr::eq(first_domain, &cell.domain) {
+                println!("Cell {} shares the same domain reference!", index);
+            } else {
+                all_same = false;
             }
         }
 
         if all_same {
-            println!("Warning: All cells share the same `possible_values` set!");
+            println!("Warning: All cells share the same domain!");
         } else {
-            println!("Each cell has a unique `possible_values` set.");
+            println!("Each cell has a unique domain.");
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::types::Tile;
+
+    /// A `width` × `height` grid of cells that may be water or land, shared
+    /// by the tests of other modules.
+    pub(crate) fn water_and_land(
+        width: usize,
+        height: usize,
+    ) -> (Grid<char>, PossibleValue<char>, PossibleValue<char>) {
+        let water = Tile::new('~', "Water", 1);
+        let land = Tile::new('g', "Land", 1);
+        let tiles = [water.clone(), land.clone()].into_iter().collect();
+        (Grid::new(width, height, tiles), water, land)
+    }
+
+    fn tiles(grid: &Grid<char>, index: usize) -> usize {
+        grid.cell(index).unwrap().possible_values().count()
+    }
+
+    #[test]
+    fn undo_restores_the_cells_changed_after_the_mark() {
+        let (mut grid, water, land) = water_and_land(2, 2);
+        grid.set_journaling(true);
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        let mark = grid.journal_len();
+        grid.cell_mut(1).unwrap().set(&land).unwrap();
+        grid.cell_mut(1).unwrap().ban(&land);
+        assert_eq!(grid.journal_len(), 3);
+
+        assert_eq!(grid.undo(mark), vec![1, 1]);
+        assert_eq!(grid.journal_len(), mark);
+        assert_eq!(tiles(&grid, 0), 1);
+        assert_eq!(tiles(&grid, 1), 2);
+
+        assert_eq!(grid.undo(0), vec![0]);
+        assert_eq!(tiles(&grid, 0), 2);
+    }
+
+    #[test]
+    fn forgotten_changes_are_kept() {
+        let (mut grid, water, land) = water_and_land(2, 2);
+        grid.set_journaling(true);
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        grid.cell_mut(1).unwrap().set(&land).unwrap();
+        grid.forget_journal(1);
+        assert_eq!(grid.journal_len(), 1);
+
+        assert_eq!(grid.undo(0), vec![1]);
+        assert_eq!(tiles(&grid, 0), 1);
+        assert_eq!(tiles(&grid, 1), 2);
+
+        grid.forget_journal(5);
+        assert_eq!(grid.journal_len(), 0);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_journaling() {
+        let (mut grid, water, _) = water_and_land(2, 2);
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        assert_eq!(grid.journal_len(), 0);
+        assert!(grid.undo(0).is_empty());
+        assert_eq!(tiles(&grid, 0), 1);
+    }
+
+    #[test]
+    fn void_cells_leave_the_tile_to_the_active_ones() {
+        let (mut grid, water, _) = water_and_land(2, 2);
+        let changed = grid
+            .apply_mask(|x, _, _| x == 0, &InactiveCells::Void(water))
+            .unwrap();
+        assert_eq!(changed, vec![2, 3]);
+        assert_eq!(tiles(&grid, 0), 2);
+        assert_eq!(tiles(&grid, 2), 1);
+    }
+
+    #[test]
+    fn exclusive_void_masks_are_undone() {
+        let (mut grid, water, land) = water_and_land(2, 2);
+        grid.set_journaling(true);
+        let changed = grid
+            .apply_mask(|x, _, _| x == 0, &InactiveCells::ExclusiveVoid(water))
+            .unwrap();
+        assert_eq!(changed, vec![0, 1, 2, 3]);
+        let remaining: Vec<_> = grid.cell(0).unwrap().possible_values().collect();
+        assert_eq!(remaining, vec![&land]);
+
+        grid.undo(0);
+        for index in 0..4 {
+            assert_eq!(tiles(&grid, index), 2);
         }
     }
 }
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use colored::Color;
+use image::{imageops, ImageResult, Rgba, RgbaImage};
+
+use crate::{
+    error::WfcError,
+    grid::Grid,
+    traits::{ColorRenderable, Renderer},
+    types::TileType,
+};
+
+/// Writes the grid to an image file, `tile_size` × `tile_size` pixels per
+/// cell. Tiles are drawn with their bitmap if one was added, otherwise with
+/// their flat color. Cells that aren't collapsed yet get the average color of
+/// their remaining tiles.
+///
+/// As a [`Renderer`] it writes the image once the run finished, rewriting it
+/// after every step would slow the run down. Use [`ImageRenderer::save`] to
+/// write a grid at any other point.
+pub struct ImageRenderer<T: TileType> {
+    output_path: PathBuf,
+    tile_size: u32,
+    bitmaps: HashMap<T, RgbaImage>,
+}
+
+impl<T: TileType> ImageRenderer<T> {
+    /// The image format is picked from the extension of `output_path`.
+    pub fn new(output_path: impl Into<PathBuf>, tile_size: u32) -> Self {
+        Self {
+            output_path: output_path.into(),
+            tile_size,
+            bitmaps: HashMap::new(),
+        }
+    }
+
+    /// Uses `bitmap` for every cell collapsed to `id`, scaled to the tile size.
+    pub fn with_bitmap(mut self, id: T, bitmap: RgbaImage) -> Self {
+        let bitmap = if bitmap.dimensions() == (self.tile_size, self.tile_size) {
+            bitmap
+        } else {
+            imageops::resize(
+                &bitmap,
+                self.tile_size,
+                self.tile_size,
+                imageops::FilterType::Nearest,
+            )
+        };
+        self.bitmaps.insert(id, bitmap);
+        self
+    }
+
+    pub fn load_bitmap(self, id: T, path: impl AsRef<Path>) -> ImageResult<Self> {
+        let bitmap = image::open(path)?.to_rgba8();
+        Ok(self.with_bitmap(id, bitmap))
+    }
+}
+
+impl<T: TileType + ColorRenderable> ImageRenderer<T> {
+    pub fn render_image(&self, grid: &Grid<T>) -> RgbaImage {
+        let size = self.tile_size;
+        // Rows of the image are the x coordinate, like in the ascii renderers.
+        // The layers of 3D grids are stacked from top to bottom.
+        let mut image = RgbaImage::new(
+            grid.height() as u32 * size,
+            (grid.width() * grid.depth()) as u32 * size,
+        );
+        for (index, cell) in grid.cells().iter().enumerate() {
+            if !grid.is_active(index) {
+                // Left transparent
+                continue;
+            }
+            let (x, y, z) = grid.coordinates(index);
+            let (left, top) = (y as u32 * size, (z * grid.width() + x) as u32 * size);
+            if let Some(bitmap) = cell
+                .get_collapsed_value()
+                .and_then(|tile| self.bitmaps.get(&tile.id))
+            {
+                imageops::replace(&mut image, bitmap, left as i64, top as i64);
+                continue;
+            }
+
+            let colors: Vec<[u8; 3]> = cell
+                .possible_values()
+                .map(|tile| color_to_rgb(tile.id.get_color()))
+                .collect();
+            let pixel = average_color(&colors);
+            for dx in 0..size {
+                for dy in 0..size {
+                    image.put_pixel(left + dx, top + dy, pixel);
+                }
+            }
+        }
+        image
+    }
+
+    pub fn save(&self, grid: &Grid<T>) -> ImageResult<()> {
+        self.render_image(grid).save(&self.output_path)
+    }
+}
+
+impl<T: TileType + ColorRenderable> Renderer<T> for ImageRenderer<T> {
+    fn render(&self, _grid: &Grid<T>) {}
+
+    fn finish(&self, grid: &Grid<T>) -> Result<(), WfcError> {
+        self.save(grid).map_err(|err| {
+            WfcError::RenderFailed(format!("{}: {}", self.output_path.display(), err))
+        })
+    }
+}
+
+fn average_color(colors: &[[u8; 3]]) -> Rgba<u8> {
+    if colors.is_empty() {
+        // Contradiction, nothing left to draw
+        return Rgba([0, 0, 0, 255]);
+    }
+    let mut sum = [0usize; 3];
+    for color in colors {
+        for (channel, value) in sum.iter_mut().zip(color) {
+            *channel += *value as usize;
+        }
+    }
+    let [r, g, b] = sum.map(|channel| (channel / colors.len()) as u8);
+    Rgba([r, g, b, 255])
+}
+
+/// Converts a terminal color to RGB, using the xterm palette for the named
+/// and 256-color variants.
+pub fn color_to_rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Black => [0, 0, 0],
+        Color::Red => [205, 0, 0],
+        Color::Green => [0, 205, 0],
+        Color::Yellow => [205, 205, 0],
+        Color::Blue => [0, 0, 238],
+        Color::Magenta => [205, 0, 205],
+        Color::Cyan => [0, 205, 205],
+        Color::White => [229, 229, 229],
+        Color::BrightBlack => [127, 127, 127],
+        Color::BrightRed => [255, 0, 0],
+        Color::BrightGreen => [0, 255, 0],
+        Color::BrightYellow => [255, 255, 0],
+        Color::BrightBlue => [92, 92, 255],
+        Color::BrightMagenta => [255, 0, 255],
+        Color::BrightCyan => [0, 255, 255],
+        Color::BrightWhite => [255, 255, 255],
+        Color::AnsiColor(index @ 0..=15) => {
+            const NAMED: [Color; 16] = [
+                Color::Black,
+                Color::Red,
+                Color::Green,
+                Color::Yellow,
+                Color::Blue,
+                Color::Magenta,
+                Color::Cyan,
+                Color::White,
+                Color::BrightBlack,
+                Color::BrightRed,
+                Color::BrightGreen,
+                Color::BrightYellow,
+                Color::BrightBlue,
+                Color::BrightMagenta,
+                Color::BrightCyan,
+                Color::BrightWhite,
+            ];
+            color_to_rgb(NAMED[index as usize])
+        }
+        Color::AnsiColor(index @ 16..=231) => {
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            [level(index / 36), level(index / 6 % 6), level(index % 6)]
+        }
+        Color::AnsiColor(index) => {
+            let gray = 8 + (index - 232) * 10;
+            [gray, gray, gray]
+        }
+        Color::TrueColor { r, g, b } => [r, g, b],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adjacency_graph::AdjacencyGraph,
+        grid::tests::water_and_land,
+        rules::{adjacency_rule::AdjacencyRule, Rule},
+        wfc::WFC,
+    };
+
+    fn run(output_path: &Path) -> Result<(), WfcError> {
+        let (grid, water, land) = water_and_land(3, 3);
+        let mut graph = AdjacencyGraph::new();
+        graph.add_self_adjacencies(vec![&water, &land]);
+        graph.add_adjacency(&water, &land);
+        let renderer = ImageRenderer::new(output_path, 2);
+        let rules: Vec<Box<dyn Rule<char>>> = vec![Box::new(AdjacencyRule::new(graph))];
+        WFC::from_grid(grid, rules, Some(renderer))
+            .with_seed(0)
+            .run()
+    }
+
+    #[test]
+    fn writes_the_image_once_the_run_finished() {
+        let path = std::env::temp_dir().join("image_renderer_finish.png");
+        let _ = std::fs::remove_file(&path);
+        run(&path).unwrap();
+        assert_eq!(image::open(&path).unwrap().to_rgba8().dimensions(), (6, 6));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reports_images_that_cant_be_written() {
+        let path = Path::new("missing directory/image.png");
+        assert!(matches!(run(path), Err(WfcError::RenderFailed(_))));
+    }
+}
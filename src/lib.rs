@@ -4,4 +4,16 @@ pub mod wfc;
 pub mod types;
 pub mod rules;
 pub mod adjacency_graph;
+pub mod direction;
+pub mod entropy;
+pub mod error;
+pub mod definition;
+pub mod image_renderer;
+pub mod overlapping;
+pub mod registry;
+pub mod selection;
+pub mod snapshot;
+pub mod symmetry;
+pub mod tile_set;
+pub mod topology;
 pub mod traits;
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use colored::Color;
+
+use crate::{
+    adjacency_graph::DirectionalAdjacencyGraph,
+    direction::Direction,
+    error::WfcError,
+    grid::Grid,
+    rules::{directional_adjacency_rule::DirectionalAdjacencyRule, Rule},
+    traits::{AsciiRenderable, ColorRenderable, Renderer},
+    types::{PossibleValue, PossibleValues, Tile, TileType},
+    wfc::WFC,
+};
+
+/// An N×N window of sample tiles. Patterns are the tiles the overlapping
+/// model runs WFC on, each output cell is the top left tile of its pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern<T: TileType> {
+    size: usize,
+    tiles: Vec<T>,
+}
+
+impl<T: TileType> TileType for Pattern<T> {}
+
+impl<T: TileType> Pattern<T> {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, dx: usize, dy: usize) -> &T {
+        &self.tiles[dx * self.size + dy]
+    }
+
+    pub fn origin(&self) -> &T {
+        self.get(0, 0)
+    }
+
+    /// Checks whether `other` can be placed next to this pattern in
+    /// `direction`, i.e. both agree on the tiles they overlap.
+    fn overlaps(&self, direction: Direction, other: &Pattern<T>) -> bool {
+        let n = self.size;
+        let (ox, oy): (isize, isize) = match direction {
+            Direction::North => (-1, 0),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
+            Direction::East => (0, 1),
+            // Patterns are learned on a square lattice
+            _ => return false,
+        };
+        for dx in 0..n {
+            for dy in 0..n {
+                let (sx, sy) = (dx as isize + ox, dy as isize + oy);
+                if sx < 0 || sy < 0 || sx >= n as isize || sy >= n as isize {
+                    continue;
+                }
+                if self.get(sx as usize, sy as usize) != other.get(dx, dy) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl<T: TileType + AsciiRenderable> AsciiRenderable for Pattern<T> {
+    fn get_ascii_representation(&self) -> char {
+        self.origin().get_ascii_representation()
+    }
+}
+
+impl<T: TileType + ColorRenderable> ColorRenderable for Pattern<T> {
+    fn get_color(&self) -> Color {
+        self.origin().get_color()
+    }
+}
+
+/// The overlapping WFC model: learns every N×N pattern of a sample along with
+/// how often it occurs, and only lets patterns touch where they overlap.
+pub struct OverlappingModel<T: TileType> {
+    n: usize,
+    patterns: Vec<PossibleValue<Pattern<T>>>,
+    adjacency_graph: DirectionalAdjacencyGraph<Pattern<T>>,
+}
+
+impl<T: TileType> OverlappingModel<T> {
+    /// Extracts the patterns of `sample`, indexed as `sample[x][y]`. With
+    /// `periodic_input` the sample wraps around, so patterns crossing its
+    /// edges are learned too.
+    pub fn new(sample: &[Vec<T>], n: usize, periodic_input: bool) -> Result<Self, WfcError> {
+        let width = sample.len();
+        let height = sample.first().map_or(0, |row| row.len());
+        if n == 0 {
+            return Err(WfcError::InvalidSample("pattern size must be at least 1".to_string()));
+        }
+        if sample.iter().any(|row| row.len() != height) {
+            return Err(WfcError::InvalidSample("all rows must have the same length".to_string()));
+        }
+        if width < n || height < n {
+            return Err(WfcError::InvalidSample(format!(
+                "sample of {}x{} is smaller than the {}x{} patterns",
+                width, height, n, n
+            )));
+        }
+
+        let (max_x, max_y) = if periodic_input {
+            (width, height)
+        } else {
+            (width - n + 1, height - n + 1)
+        };
+        // Keep discovery order so pattern names, and with them seeded runs,
+        // are stable.
+        let mut order: Vec<Pattern<T>> = Vec::new();
+        let mut counts: HashMap<Pattern<T>, i32> = HashMap::new();
+        for x in 0..max_x {
+            for y in 0..max_y {
+                let mut tiles = Vec::with_capacity(n * n);
+                for dx in 0..n {
+                    for dy in 0..n {
+                        tiles.push(sample[(x + dx) % width][(y + dy) % height].clone());
+                    }
+                }
+                let pattern = Pattern { size: n, tiles };
+                let count = counts.entry(pattern.clone()).or_insert(0);
+                if *count == 0 {
+                    order.push(pattern);
+                }
+                *count += 1;
+            }
+        }
+
+        let patterns: Vec<PossibleValue<Pattern<T>>> = order
+            .into_iter()
+            .enumerate()
+            .map(|(index, pattern)| {
+                let weight = counts[&pattern];
+                Tile::new(pattern, &format!("pattern {}", index), weight)
+            })
+            .collect();
+
+        let mut adjacency_graph = DirectionalAdjacencyGraph::new();
+        for a in patterns.iter() {
+            for b in patterns.iter() {
+                for direction in [Direction::South, Direction::East] {
+                    if a.id.overlaps(direction, &b.id) {
+                        adjacency_graph.add_adjacency(a, direction, b);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            n,
+            patterns,
+            adjacency_graph,
+        })
+    }
+
+    pub fn patterns(&self) -> PossibleValues<Pattern<T>> {
+        self.patterns.iter().cloned().collect()
+    }
+
+    pub fn rule(&self) -> DirectionalAdjacencyRule<Pattern<T>> {
+        DirectionalAdjacencyRule::new(self.adjacency_graph.clone())
+    }
+
+    /// Creates a solver whose grid of patterns covers an output of
+    /// `width` × `height` tiles.
+    pub fn build<R: Renderer<Pattern<T>>>(
+        &self,
+        width: usize,
+        height: usize,
+        renderer: Option<R>,
+    ) -> Result<WFC<Pattern<T>, R>, WfcError>
+    where
+        T: 'static,
+    {
+        if width < self.n || height < self.n {
+            return Err(WfcError::InvalidSample(format!(
+                "output of {}x{} is smaller than the {}x{} patterns",
+                width, height, self.n, self.n
+            )));
+        }
+        let rules: Vec<Box<dyn Rule<Pattern<T>>>> = vec![Box::new(self.rule())];
+        Ok(WFC::new(
+            width - self.n + 1,
+            height - self.n + 1,
+            self.patterns(),
+            rules,
+            renderer,
+        ))
+    }
+
+    /// Assembles the output tiles of a solved pattern grid, `None` if some
+    /// cells are not collapsed yet.
+    pub fn output(&self, grid: &Grid<Pattern<T>>) -> Option<Vec<Vec<T>>> {
+        let width = grid.width() + self.n - 1;
+        let height = grid.height() + self.n - 1;
+        let mut output = Vec::with_capacity(width);
+        for x in 0..width {
+            let mut row = Vec::with_capacity(height);
+            for y in 0..height {
+                let (px, py) = (x.min(grid.width() - 1), y.min(grid.height() - 1));
+                let pattern = grid.get_cell(px, py)?.get_collapsed_value()?;
+                row.push(pattern.id.get(x - px, y - py).clone());
+            }
+            output.push(row);
+        }
+        Some(output)
+    }
+}
+
+/// Parses a sample drawn as text, one row per line. Empty lines are skipped.
+pub fn parse_ascii_sample(text: &str) -> Vec<Vec<char>> {
+    text.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.chars().collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::AsciiRenderer;
+
+    /// A pattern drawn as rows separated by `/`, e.g. `ab/cd`.
+    fn pattern(rows: &str) -> Pattern<char> {
+        let rows = parse_ascii_sample(&rows.replace('/', "\n"));
+        Pattern {
+            size: rows.len(),
+            tiles: rows.concat(),
+        }
+    }
+
+    fn weights(model: &OverlappingModel<char>) -> Vec<(Pattern<char>, i32)> {
+        model
+            .patterns
+            .iter()
+            .map(|tile| (tile.id.clone(), tile.weight))
+            .collect()
+    }
+
+    #[test]
+    fn patterns_overlap_to_the_south_and_east() {
+        let a = pattern("ab/cd");
+        assert!(a.overlaps(Direction::South, &pattern("cd/ef")));
+        assert!(!a.overlaps(Direction::South, &pattern("ab/ef")));
+        assert!(a.overlaps(Direction::East, &pattern("be/df")));
+        assert!(!a.overlaps(Direction::East, &pattern("ae/cf")));
+        assert!(pattern("cd/ef").overlaps(Direction::North, &a));
+        assert!(pattern("be/df").overlaps(Direction::West, &a));
+    }
+
+    #[test]
+    fn counts_patterns_inside_the_sample() {
+        let sample = parse_ascii_sample("aab\naab\naab");
+        let model = OverlappingModel::new(&sample, 2, false).unwrap();
+        assert_eq!(
+            weights(&model),
+            vec![(pattern("aa/aa"), 2), (pattern("ab/ab"), 2)]
+        );
+    }
+
+    #[test]
+    fn counts_patterns_crossing_the_edges_of_a_periodic_sample() {
+        let sample = parse_ascii_sample("aab\naab\naab");
+        let model = OverlappingModel::new(&sample, 2, true).unwrap();
+        assert_eq!(
+            weights(&model),
+            vec![
+                (pattern("aa/aa"), 3),
+                (pattern("ab/ab"), 3),
+                (pattern("ba/ba"), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn output_takes_the_edges_from_the_last_patterns() {
+        let sample = parse_ascii_sample("abc\ndef");
+        let model = OverlappingModel::new(&sample, 2, false).unwrap();
+        let mut grid = Grid::new(1, 2, model.patterns());
+        assert_eq!(model.output(&grid), None);
+        for (y, tile) in model.patterns.iter().enumerate() {
+            grid.get_cell_mut(0, y).unwrap().set(tile).unwrap();
+        }
+        assert_eq!(model.output(&grid), Some(sample));
+    }
+
+    #[test]
+    fn rejects_unusable_samples() {
+        let sample = parse_ascii_sample("ab\ncd");
+        let ragged = parse_ascii_sample("ab\nc");
+        for result in [
+            OverlappingModel::new(&sample, 0, false),
+            OverlappingModel::new(&ragged, 1, false),
+            OverlappingModel::new(&sample, 3, true),
+        ] {
+            assert!(matches!(result, Err(WfcError::InvalidSample(_))));
+        }
+
+        let model = OverlappingModel::new(&sample, 2, true).unwrap();
+        assert!(matches!(
+            model.build::<AsciiRenderer>(1, 4, None),
+            Err(WfcError::InvalidSample(_))
+        ));
+    }
+}
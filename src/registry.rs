@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use crate::{
+    tile_set::TileSet,
+    types::{PossibleValue, PossibleValues, TileType},
+};
+
+/// Interns the tiles of a run, giving each a dense index used by [`TileSet`].
+///
+/// Tiles are ordered by name so indices, and with them seeded runs, don't
+/// depend on the iteration order of the `PossibleValues` they came from.
+#[derive(Debug)]
+pub struct TileRegistry<T: TileType> {
+    tiles: Vec<PossibleValue<T>>,
+    indices: HashMap<PossibleValue<T>, usize>,
+}
+
+impl<T: TileType> TileRegistry<T> {
+    pub fn new(possible_values: &PossibleValues<T>) -> Self {
+        let mut tiles: Vec<PossibleValue<T>> = possible_values.iter().cloned().collect();
+        tiles.sort_by_cached_key(|tile| (tile.name.clone(), tile.weight, format!("{:?}", tile.id)));
+        let indices = tiles
+            .iter()
+            .enumerate()
+            .map(|(index, tile)| (tile.clone(), index))
+            .collect();
+        Self { tiles, indices }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn tiles(&self) -> &[PossibleValue<T>] {
+        &self.tiles
+    }
+
+    pub fn get(&self, index: usize) -> Option<&PossibleValue<T>> {
+        self.tiles.get(index)
+    }
+
+    pub fn index_of(&self, tile: &PossibleValue<T>) -> Option<usize> {
+        self.indices.get(tile).copied()
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&PossibleValue<T>> {
+        self.tiles.iter().find(|tile| tile.name == name)
+    }
+
+    /// Set of every registered tile.
+    pub fn full_set(&self) -> TileSet {
+        TileSet::full(self.len())
+    }
+
+    pub fn empty_set(&self) -> TileSet {
+        TileSet::empty(self.len())
+    }
+
+    /// Converts tiles to a set, tiles that aren't registered are skipped.
+    pub fn to_set<'a>(&self, tiles: impl IntoIterator<Item = &'a PossibleValue<T>>) -> TileSet
+    where
+        T: 'a,
+    {
+        let mut set = self.empty_set();
+        for index in tiles.into_iter().filter_map(|tile| self.index_of(tile)) {
+            set.insert(index);
+        }
+        set
+    }
+}
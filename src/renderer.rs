@@ -1,10 +1,12 @@
 use std::{
     io::{stdout, Write},
     thread::sleep,
+    time::Duration,
 };
 
 use crate::{
     grid::Grid,
+    topology::Lattice,
     traits::{AsciiRenderable, ColorRenderable, Renderer},
     types::TileType,
 };
@@ -14,47 +16,90 @@ use crossterm::{
     terminal::{Clear, ClearType},
 };
 
-pub struct AsciiRenderer;
+/// Redraws the whole terminal on every render, pausing `frame_delay` between
+/// frames so the generation can be followed.
+pub struct AsciiRenderer {
+    pub frame_delay: Duration,
+}
+
+impl AsciiRenderer {
+    pub fn new(frame_delay: Duration) -> Self {
+        Self { frame_delay }
+    }
+}
+
+impl Default for AsciiRenderer {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(15))
+    }
+}
 
 impl<T: TileType + AsciiRenderable + ColorRenderable> Renderer<T> for AsciiRenderer {
     fn render(&self, grid: &Grid<T>) {
         render_ascii(grid);
         // simple_render(grid);
         // debug_render(grid);
+        if !self.frame_delay.is_zero() {
+            sleep(self.frame_delay);
+        }
     }
 }
 
+/// Hex lattices are drawn with a space between cells and every odd row
+/// indented by one, so each row sits between the cells of its neighbors.
+fn hex_layout<T: TileType>(grid: &Grid<T>, x: usize) -> (&'static str, &'static str) {
+    match grid.lattice().map(|topology| topology.lattice) {
+        Some(Lattice::Hex) if x % 2 == 1 => (" ", " "),
+        Some(Lattice::Hex) => ("", " "),
+        _ => ("", ""),
+    }
+}
+
+/// Prints a header before each layer of a 3D grid, nothing for 2D grids.
+fn print_layer_header<T: TileType>(grid: &Grid<T>, z: usize) {
+    if grid.depth() > 1 {
+        println!("z = {}", z);
+    }
+}
+
+/// Inactive cells of a masked grid are left blank.
+fn is_active<T: TileType>(grid: &Grid<T>, x: usize, y: usize, z: usize) -> bool {
+    grid.index_3d(x, y, z)
+        .is_some_and(|index| grid.is_active(index))
+}
+
 pub fn render_ascii<T: TileType + AsciiRenderable + ColorRenderable>(grid: &Grid<T>) {
     let mut stdout = stdout();
     execute!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0)).unwrap();
-    for row in grid.get_cells() {
-        for cell in row {
-            if let Some(tile) = cell.get_collapsed_value() {
-                let ascii = tile.id.get_ascii_representation();
-                let color = tile.id.get_color();
-                print!("{}", ascii.to_string().color(color))
-            } else {
-                print!("#")
-            }
-        }
-        println!();
-    }
+    simple_render(grid);
     stdout.flush().unwrap();
-    let sleep_time = std::time::Duration::from_millis(15);
-    sleep(sleep_time);
 }
 
+/// Prints every layer of the grid, one after the other.
 pub fn simple_render<T: TileType + AsciiRenderable + ColorRenderable>(grid: &Grid<T>) {
-    for row in grid.get_cells() {
-        for cell in row {
+    for z in 0..grid.depth() {
+        print_layer_header(grid, z);
+        render_slice(grid, z);
+    }
+}
+
+/// Prints the layer `z` of the grid.
+pub fn render_slice<T: TileType + AsciiRenderable + ColorRenderable>(grid: &Grid<T>, z: usize) {
+    for (x, row) in grid.rows(z).enumerate() {
+        let (indent, separator) = hex_layout(grid, x);
+        print!("{}", indent);
+        for (y, cell) in row.iter().enumerate() {
             // println!("cell: {:?}", cell);
-            if let Some(tile) = cell.get_collapsed_value() {
+            if !is_active(grid, x, y, z) {
+                print!(" ");
+            } else if let Some(tile) = cell.get_collapsed_value() {
                 let ascii = tile.id.get_ascii_representation();
                 let color = tile.id.get_color();
                 print!("{}", ascii.to_string().color(color))
             } else {
                 print!("{}", "#".color("white"))
             }
+            print!("{}", separator);
         }
         println!();
     }
@@ -63,44 +108,47 @@ pub fn simple_render<T: TileType + AsciiRenderable + ColorRenderable>(grid: &Gri
 pub fn debug_render<T: TileType + AsciiRenderable + ColorRenderable>(grid: &Grid<T>) {
     // Determine the maximum number of possibilities in any cell
     let max_options = grid
-        .get_cells()
+        .cells()
         .iter()
-        .flatten()
-        .map(|cell| cell.possible_values.len())
+        .map(|cell| cell.possible_count())
         .max()
         .unwrap_or(1); // Ensure at least 1 for collapsed cells
 
-    for row in grid.get_cells() {
-        for cell in row {
-            if let Some(tile) = cell.get_collapsed_value() {
-                let ascii = tile.id.get_ascii_representation();
-                let color = tile.id.get_color();
-                print!(
-                    "[{:^width$}]",
-                    ascii.to_string().color(color),
-                    width = max_options
-                );
-            } else {
-                let mut possibilities: Vec<String> = cell
-                    .possible_values
-                    .iter()
-                    .map(|tile| {
-                        let ascii = tile.id.get_ascii_representation();
-                        let color = tile.id.get_color();
-                        ascii.to_string().color(color).to_string()
-                    })
-                    .collect();
+    for z in 0..grid.depth() {
+        print_layer_header(grid, z);
+        for (x, row) in grid.rows(z).enumerate() {
+            for (y, cell) in row.iter().enumerate() {
+                if !is_active(grid, x, y, z) {
+                    print!("[{:^width$}]", "", width = max_options);
+                } else if let Some(tile) = cell.get_collapsed_value() {
+                    let ascii = tile.id.get_ascii_representation();
+                    let color = tile.id.get_color();
+                    print!(
+                        "[{:^width$}]",
+                        ascii.to_string().color(color),
+                        width = max_options
+                    );
+                } else {
+                    let mut possibilities: Vec<String> = cell
+                        .possible_values()
+                        .map(|tile| {
+                            let ascii = tile.id.get_ascii_representation();
+                            let color = tile.id.get_color();
+                            ascii.to_string().color(color).to_string()
+                        })
+                        .collect();
 
-                // Ensure the printed length is consistent
-                while possibilities.len() < max_options {
-                    possibilities.push(" ".to_string()); // Add padding
-                }
+                    // Ensure the printed length is consistent
+                    while possibilities.len() < max_options {
+                        possibilities.push(" ".to_string()); // Add padding
+                    }
 
-                print!("[{}]", possibilities.join(""));
+                    print!("[{}]", possibilities.join(""));
+                }
+                print!(" "); // Space between cells
             }
-            print!(" "); // Space between cells
+            println!();
         }
         println!();
     }
-    println!();
 }
@@ -1,17 +1,23 @@
 use crate::{
     adjacency_graph::{self, AdjacencyGraph},
-    types::{PossibleValues, TileType},
+    error::WfcError,
+    tile_set::TileSet,
+    types::TileType,
 };
 
-use super::Rule;
+use super::{RegistryCache, Rule};
 
 pub struct AdjacencyRule<T: TileType> {
     adjacency_graph: adjacency_graph::AdjacencyGraph<T>,
+    masks: RegistryCache<T, Vec<TileSet>>,
 }
 
 impl<T: TileType> AdjacencyRule<T> {
     pub fn new(adjacency_graph: AdjacencyGraph<T>) -> Self {
-        Self { adjacency_graph }
+        Self {
+            adjacency_graph,
+            masks: RegistryCache::new(),
+        }
     }
 }
 
@@ -19,38 +25,79 @@ impl<T: TileType> Rule<T> for AdjacencyRule<T> {
     fn propagate_constraints(
         &self,
         grid: &mut crate::grid::Grid<T>,
-        x: usize,
-        y: usize,
-    ) -> Result<Vec<(usize, usize)>, String> {
-        let cell = grid
-            .get_cell(x, y)
-            .ok_or_else(|| format!("Cell at ({}, {}) not found", x, y))?
-            .clone();
+        cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        let masks = self.masks.get(grid.registry(), || {
+            self.adjacency_graph.compile(grid.registry())
+        });
+        let domain = grid
+            .cell(cell)
+            .ok_or(WfcError::UnknownCell { cell })?
+            .domain();
+        let mut allowed_neighbors = grid.registry().empty_set();
+        for index in domain.iter() {
+            allowed_neighbors.union_with(&masks[index]);
+        }
+
         let mut affected_cells = Vec::new();
-        let valid_coordinates = grid.get_valid_coordinates(x, y);
-        // println!("valid_coordinates: {:?}", valid_coordinates);
-
-        for (nx, ny) in valid_coordinates {
-            let neighbor_cell = grid.get_cell_mut(nx, ny).unwrap();
-            // println!("neighbor_cell: {},{} {:?}", nx, ny, neighbor_cell);
-            // if neighbor_cell.is_collapsed() {
-                // println!("Skipping collapsed cell");
-                // continue;
-            // }
-            let mut allowed_neighbors = PossibleValues::new();
-            for possible_value in cell.possible_values.iter() {
-                if let Some(valid_neighbors) =
-                    self.adjacency_graph.get_valid_neighbors(possible_value)
-                {
-                    allowed_neighbors.extend(valid_neighbors.clone());
-                }
-            }
+        let neighbors = grid.get_neighbors(cell);
+        // println!("neighbors: {:?}", neighbors);
 
+        for neighbor in neighbors {
+            // Checked before taking the cell mutably, which journals it
+            if grid
+                .cell(neighbor)
+                .unwrap()
+                .domain()
+                .is_subset(&allowed_neighbors)
+            {
+                continue;
+            }
+            let neighbor_cell = grid.cell_mut(neighbor).unwrap();
+            // println!("neighbor_cell: {} {:?}", neighbor, neighbor_cell);
             if neighbor_cell.constrain(&allowed_neighbors) {
-                affected_cells.push((nx, ny));
+                if neighbor_cell.is_contradiction() {
+                    return Err(WfcError::Contradiction {
+                        cell: neighbor,
+                        rule: "AdjacencyRule".to_string(),
+                    });
+                }
+                affected_cells.push(neighbor);
             }
         }
 
         Ok(affected_cells)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::Grid, types::Tile};
+
+    #[test]
+    fn follows_the_tiles_of_the_current_grid() {
+        let beach = Tile::new('.', "Beach", 1);
+        let land = Tile::new('g', "Land", 1);
+        let water = Tile::new('~', "Water", 1);
+        let mut graph = AdjacencyGraph::new();
+        graph.add_self_adjacencies(vec![&land, &water]);
+        graph.add_adjacency(&beach, &water);
+        let rule = AdjacencyRule::new(graph);
+        let tiles = |grid: &Grid<char>| -> Vec<char> {
+            let cell = grid.cell(1).unwrap();
+            cell.possible_values().map(|tile| tile.id).collect()
+        };
+
+        let mut grid = Grid::new(1, 2, [land.clone(), water.clone()].into_iter().collect());
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        rule.propagate_constraints(&mut grid, 0).unwrap();
+        assert_eq!(tiles(&grid), vec!['~']);
+
+        // Beach comes first in the new registry, shifting the other indices
+        let mut grid = Grid::new(1, 2, [beach, land, water.clone()].into_iter().collect());
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        rule.propagate_constraints(&mut grid, 0).unwrap();
+        assert_eq!(tiles(&grid), vec!['.', '~']);
+    }
+}
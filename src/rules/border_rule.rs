@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use crate::{
+    direction::Direction,
+    error::WfcError,
+    grid::Grid,
+    tile_set::TileSet,
+    topology::LatticeTopology,
+    types::{PossibleValue, PossibleValues, TileType},
+};
+
+use super::{RegistryCache, Rule};
+
+/// Restricts the tiles on the edges of a lattice grid, e.g. to keep the map
+/// boundary all water or all walls. Each side has its own allowed tiles:
+/// `North` is the top row, `South` the bottom row, `West` and `East` the left
+/// and right columns, `Down` and `Up` the first and last layer of a 3D grid.
+/// Corner cells get the tiles allowed on both of their sides.
+///
+/// The border is applied before the first observation and propagated like
+/// any other constraint, so the cells further in are narrowed accordingly.
+/// Sides along a wrapping axis aren't borders and are left alone, as are
+/// inactive cells and grids that aren't lattices.
+#[derive(Debug, Clone)]
+pub struct BorderRule<T: TileType> {
+    sides: HashMap<Direction, PossibleValues<T>>,
+    sides_compiled: RegistryCache<T, Vec<(Direction, TileSet)>>,
+}
+
+impl<T: TileType> Default for BorderRule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TileType> BorderRule<T> {
+    pub fn new() -> Self {
+        Self {
+            sides: HashMap::new(),
+            sides_compiled: RegistryCache::new(),
+        }
+    }
+
+    /// Allows only `tiles` on the `side` of the grid, one of
+    /// [`Direction::CUBIC`].
+    pub fn set_side(&mut self, side: Direction, tiles: Vec<&PossibleValue<T>>) {
+        self.sides
+            .insert(side, tiles.into_iter().cloned().collect());
+        self.sides_compiled = RegistryCache::new();
+    }
+
+    /// Allows only `tiles` on the four sides of the grid.
+    pub fn set_all_sides(&mut self, tiles: Vec<&PossibleValue<T>>) {
+        for side in Direction::SQUARE {
+            self.set_side(side, tiles.clone());
+        }
+    }
+
+    fn compile(&self, grid: &Grid<T>) -> Vec<(Direction, TileSet)> {
+        Direction::CUBIC
+            .iter()
+            .filter_map(|side| {
+                let tiles = self.sides.get(side)?;
+                Some((*side, grid.registry().to_set(tiles)))
+            })
+            .collect()
+    }
+}
+
+/// Indices of the cells on `side` of the lattice, none if the side is along
+/// a wrapping axis.
+fn side_cells(lattice: &LatticeTopology, side: Direction) -> Vec<usize> {
+    let (width, height, depth) = (lattice.width, lattice.height, lattice.depth);
+    let (xs, ys, zs) = match side {
+        Direction::North if !lattice.wraps_x() => (0..width.min(1), 0..height, 0..depth),
+        Direction::South if !lattice.wraps_x() => {
+            (width.saturating_sub(1)..width, 0..height, 0..depth)
+        }
+        Direction::West if !lattice.wrap_y => (0..width, 0..height.min(1), 0..depth),
+        Direction::East if !lattice.wrap_y => {
+            (0..width, height.saturating_sub(1)..height, 0..depth)
+        }
+        Direction::Down => (0..width, 0..height, 0..depth.min(1)),
+        Direction::Up => (0..width, 0..height, depth.saturating_sub(1)..depth),
+        _ => return Vec::new(),
+    };
+    let mut cells = Vec::new();
+    for z in zs {
+        for x in xs.clone() {
+            cells.extend(ys.clone().filter_map(|y| lattice.index(x, y, z)));
+        }
+    }
+    cells
+}
+
+impl<T: TileType> Rule<T> for BorderRule<T> {
+    fn propagate_constraints(
+        &self,
+        _grid: &mut Grid<T>,
+        _cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        Ok(Vec::new())
+    }
+
+    fn propagate_global_constraints(&self, grid: &mut Grid<T>) -> Result<Vec<usize>, WfcError> {
+        let sides = self
+            .sides_compiled
+            .get(grid.registry(), || self.compile(grid));
+        // Only the tiles of each side are cached, which cells lie on it
+        // depends on the size and wrapping of this grid
+        let Some(lattice) = grid.lattice().copied() else {
+            return Ok(Vec::new());
+        };
+        let mut affected_cells = Vec::new();
+        for (side, allowed) in sides.iter() {
+            for cell in side_cells(&lattice, *side) {
+                if !grid.is_active(cell) || grid.cell(cell).unwrap().domain().is_subset(allowed) {
+                    continue;
+                }
+                let cell_state = grid.cell_mut(cell).unwrap();
+                cell_state.constrain(allowed);
+                if cell_state.is_contradiction() {
+                    return Err(WfcError::Contradiction {
+                        cell,
+                        rule: "BorderRule".to_string(),
+                    });
+                }
+                affected_cells.push(cell);
+            }
+        }
+        Ok(affected_cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adjacency_graph::AdjacencyGraph, renderer::AsciiRenderer,
+        rules::adjacency_rule::AdjacencyRule, types::Tile, wfc::WFC,
+    };
+
+    /// Runs the initial propagation and the first observation, then returns
+    /// how many cells of the top row may still be land.
+    fn land_on_top_row(wrap_after_preset: bool) -> usize {
+        let water = Tile::new('~', "Water", 1);
+        let land = Tile::new('g', "Land", 1);
+        let mut graph = AdjacencyGraph::new();
+        graph.add_self_adjacencies(vec![&water, &land]);
+        graph.add_adjacency(&water, &land);
+        let mut border = BorderRule::new();
+        border.set_side(Direction::North, vec![&water]);
+        let rules: Vec<Box<dyn Rule<char>>> =
+            vec![Box::new(AdjacencyRule::new(graph)), Box::new(border)];
+
+        let tiles = [water, land.clone()].into_iter().collect();
+        let mut wfc = WFC::<char, AsciiRenderer>::new(6, 6, tiles, rules, None).with_seed(1);
+        wfc.preset_tile(land.clone(), 3, 3).unwrap();
+        if wrap_after_preset {
+            wfc = wfc.with_wrapping(true, false);
+        }
+        wfc.step().unwrap();
+        (0..6)
+            .filter(|&y| {
+                let cell = wfc.grid.cell(wfc.grid.index(0, y).unwrap()).unwrap();
+                cell.possible_values().any(|tile| tile == &land)
+            })
+            .count()
+    }
+
+    #[test]
+    fn restricts_the_top_row() {
+        assert_eq!(land_on_top_row(false), 0);
+    }
+
+    #[test]
+    fn follows_wrapping_changed_after_the_first_propagation() {
+        assert!(land_on_top_row(true) > 0);
+    }
+}
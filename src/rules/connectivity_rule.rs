@@ -0,0 +1,336 @@
+use crate::{
+    error::WfcError,
+    grid::Grid,
+    tile_set::TileSet,
+    types::{PossibleValue, PossibleValues, TileType},
+};
+
+use super::{RegistryCache, Rule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Walkability {
+    /// Inactive, or none of the remaining tiles are walkable.
+    Blocked,
+    Possible,
+    /// Every remaining tile is walkable.
+    Required,
+}
+
+/// The walkable tiles and all others, as sets over the registry of the grid.
+#[derive(Debug, Clone)]
+struct Compiled {
+    walkable: TileSet,
+    blocked: TileSet,
+}
+
+/// Keeps the cells with walkable tiles in one connected region, so generated
+/// dungeons or islands have no unreachable pockets. With
+/// [`ConnectivityRule::with_path`] two cells, e.g. an entrance and an exit,
+/// are also made walkable and linked.
+///
+/// Cells that can't reach the region lose their walkable tiles, and cells the
+/// region can't do without, because they are the only link between walkable
+/// cells, lose all others.
+#[derive(Debug, Clone)]
+pub struct ConnectivityRule<T: TileType> {
+    walkable: PossibleValues<T>,
+    path: Option<(usize, usize)>,
+    compiled: RegistryCache<T, Compiled>,
+}
+
+impl<T: TileType> ConnectivityRule<T> {
+    pub fn new(walkable: Vec<&PossibleValue<T>>) -> Self {
+        Self {
+            walkable: walkable.into_iter().cloned().collect(),
+            path: None,
+            compiled: RegistryCache::new(),
+        }
+    }
+
+    /// Also requires the cells at index `entrance` and `exit` to be walkable
+    /// and connected, see [`Grid::index`].
+    pub fn with_path(mut self, entrance: usize, exit: usize) -> Self {
+        self.path = Some((entrance, exit));
+        self
+    }
+
+    fn compile(&self, grid: &Grid<T>) -> Compiled {
+        let registry = grid.registry();
+        Compiled {
+            walkable: registry.to_set(&self.walkable),
+            blocked: registry.to_set(
+                registry
+                    .tiles()
+                    .iter()
+                    .filter(|tile| !self.walkable.contains(*tile)),
+            ),
+        }
+    }
+
+    /// Restricts `cells` to `allowed`, returns the ones that changed.
+    fn constrain(
+        grid: &mut Grid<T>,
+        cells: impl IntoIterator<Item = usize>,
+        allowed: &TileSet,
+    ) -> Result<Vec<usize>, WfcError> {
+        let mut affected_cells = Vec::new();
+        for cell in cells {
+            let domain = grid
+                .cell(cell)
+                .ok_or(WfcError::UnknownCell { cell })?
+                .domain();
+            if domain.is_subset(allowed) {
+                continue;
+            }
+            let cell_state = grid.cell_mut(cell).unwrap();
+            cell_state.constrain(allowed);
+            if cell_state.is_contradiction() {
+                return Err(WfcError::Contradiction {
+                    cell,
+                    rule: "ConnectivityRule".to_string(),
+                });
+            }
+            affected_cells.push(cell);
+        }
+        Ok(affected_cells)
+    }
+}
+
+impl<T: TileType> Rule<T> for ConnectivityRule<T> {
+    fn propagate_constraints(
+        &self,
+        _grid: &mut Grid<T>,
+        _cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        Ok(Vec::new())
+    }
+
+    fn propagate_global_constraints(&self, grid: &mut Grid<T>) -> Result<Vec<usize>, WfcError> {
+        let compiled = self.compiled.get(grid.registry(), || self.compile(grid));
+        if let Some((entrance, exit)) = self.path {
+            let affected_cells = Self::constrain(grid, [entrance, exit], &compiled.walkable)?;
+            if !affected_cells.is_empty() {
+                return Ok(affected_cells);
+            }
+        }
+
+        let walkability: Vec<Walkability> = (0..grid.len())
+            .map(|cell| {
+                let domain = grid.cell(cell).unwrap().domain();
+                if !grid.is_active(cell) || !domain.intersects(&compiled.walkable) {
+                    Walkability::Blocked
+                } else if domain.is_subset(&compiled.walkable) {
+                    Walkability::Required
+                } else {
+                    Walkability::Possible
+                }
+            })
+            .collect();
+        let required: Vec<usize> = (0..grid.len())
+            .filter(|&cell| walkability[cell] == Walkability::Required)
+            .collect();
+        let Some(&root) = required.first() else {
+            // Nothing to connect yet
+            return Ok(Vec::new());
+        };
+
+        // Depth-first search from a required cell through the cells that may
+        // be walkable, finding the cells whose removal would split the
+        // required ones (Tarjan's articulation points)
+        let is_required = |cell: usize| (walkability[cell] == Walkability::Required) as usize;
+        let mut discovered = vec![usize::MAX; grid.len()];
+        let mut low = vec![0; grid.len()];
+        // Required cells in the search subtree of every cell
+        let mut below = vec![0; grid.len()];
+        let mut critical = Vec::new();
+        let mut time = 0;
+        discovered[root] = time;
+        below[root] = 1;
+        // Neighbors aren't compiled with the tile sets, grids of another size
+        // or wrapping can share the same registry
+        let mut stack = vec![(root, grid.get_neighbors(root), 0)];
+        while let Some((cell, neighbors, next)) = stack.last_mut() {
+            let cell = *cell;
+            if let Some(&neighbor) = neighbors.get(*next) {
+                *next += 1;
+                if walkability[neighbor] == Walkability::Blocked {
+                    continue;
+                }
+                if discovered[neighbor] == usize::MAX {
+                    time += 1;
+                    discovered[neighbor] = time;
+                    low[neighbor] = time;
+                    below[neighbor] = is_required(neighbor);
+                    stack.push((neighbor, grid.get_neighbors(neighbor), 0));
+                } else {
+                    low[cell] = low[cell].min(discovered[neighbor]);
+                }
+                continue;
+            }
+
+            stack.pop();
+            if let Some(&(parent, _, _)) = stack.last() {
+                low[parent] = low[parent].min(low[cell]);
+                below[parent] += below[cell];
+                let separated = low[cell] >= discovered[parent];
+                let outside = required.len() - below[cell] - is_required(parent);
+                if separated && below[cell] > 0 && outside > 0 && is_required(parent) == 0 {
+                    critical.push(parent);
+                }
+            }
+        }
+
+        if let Some(&cell) = required
+            .iter()
+            .find(|&&cell| discovered[cell] == usize::MAX)
+        {
+            return Err(WfcError::Contradiction {
+                cell,
+                rule: "ConnectivityRule".to_string(),
+            });
+        }
+
+        // Cells out of reach can't be walkable, the ones linking the region
+        // must be
+        let unreachable = (0..grid.len()).filter(|&cell| {
+            walkability[cell] == Walkability::Possible && discovered[cell] == usize::MAX
+        });
+        let mut affected_cells = Self::constrain(grid, unreachable, &compiled.blocked)?;
+        affected_cells.extend(Self::constrain(grid, critical, &compiled.walkable)?);
+        affected_cells.sort_unstable();
+        affected_cells.dedup();
+        Ok(affected_cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Tile;
+
+    struct Dungeon {
+        grid: Grid<char>,
+        floor: PossibleValue<char>,
+        wall: PossibleValue<char>,
+    }
+
+    /// A `width` × `height` grid of cells that may be floor or wall, with
+    /// the cells in `layout` set to floor (`.`) or wall (`#`).
+    fn dungeon(width: usize, height: usize, layout: &[(usize, char)]) -> Dungeon {
+        let floor = Tile::new('.', "Floor", 1);
+        let wall = Tile::new('#', "Wall", 1);
+        let tiles = [floor.clone(), wall.clone()].into_iter().collect();
+        let mut grid = Grid::new(width, height, tiles);
+        for &(cell, id) in layout {
+            let tile = if id == '.' { &floor } else { &wall };
+            grid.cell_mut(cell).unwrap().set(tile).unwrap();
+        }
+        Dungeon { grid, floor, wall }
+    }
+
+    impl Dungeon {
+        fn rule(&self) -> ConnectivityRule<char> {
+            ConnectivityRule::new(vec![&self.floor])
+        }
+
+        fn tiles(&self, cell: usize) -> Vec<char> {
+            let cell = self.grid.cell(cell).unwrap();
+            cell.possible_values().map(|tile| tile.id).collect()
+        }
+    }
+
+    #[test]
+    fn forces_the_only_link_between_floors() {
+        // . ? ? ? .
+        let mut dungeon = dungeon(1, 5, &[(0, '.'), (4, '.')]);
+        let affected = dungeon
+            .rule()
+            .propagate_global_constraints(&mut dungeon.grid)
+            .unwrap();
+        assert_eq!(affected, vec![1, 2, 3]);
+        for cell in 1..4 {
+            assert_eq!(dungeon.tiles(cell), vec!['.']);
+        }
+    }
+
+    #[test]
+    fn leaves_cells_with_alternative_paths_open() {
+        // . ? ?
+        // ? ? ?
+        // ? ? .
+        let mut dungeon = dungeon(3, 3, &[(0, '.'), (8, '.')]);
+        let affected = dungeon
+            .rule()
+            .propagate_global_constraints(&mut dungeon.grid)
+            .unwrap();
+        assert!(affected.is_empty());
+    }
+
+    #[test]
+    fn forces_a_winding_corridor() {
+        // . ? #
+        // # ? #
+        // # ? .
+        let layout = [(0, '.'), (2, '#'), (3, '#'), (5, '#'), (6, '#'), (8, '.')];
+        let mut dungeon = dungeon(3, 3, &layout);
+        let affected = dungeon
+            .rule()
+            .propagate_global_constraints(&mut dungeon.grid)
+            .unwrap();
+        assert_eq!(affected, vec![1, 4, 7]);
+    }
+
+    #[test]
+    fn blocks_cells_out_of_reach() {
+        // . ? # ? ?
+        let mut dungeon = dungeon(1, 5, &[(0, '.'), (2, '#')]);
+        let affected = dungeon
+            .rule()
+            .propagate_global_constraints(&mut dungeon.grid)
+            .unwrap();
+        assert_eq!(affected, vec![3, 4]);
+        assert_eq!(dungeon.tiles(1), vec!['.', '#']);
+        assert_eq!(dungeon.tiles(3), vec!['#']);
+    }
+
+    #[test]
+    fn reports_separated_floors() {
+        // . ? # ? .
+        let mut dungeon = dungeon(1, 5, &[(0, '.'), (2, '#'), (4, '.')]);
+        assert!(matches!(
+            dungeon
+                .rule()
+                .propagate_global_constraints(&mut dungeon.grid),
+            Err(WfcError::Contradiction { .. })
+        ));
+    }
+
+    #[test]
+    fn follows_the_current_layout() {
+        // . # ? ? ?, wrapping around after the rule already ran once
+        let Dungeon { grid, floor, wall } = dungeon(1, 5, &[(0, '.'), (1, '#')]);
+        let rule = ConnectivityRule::new(vec![&floor]);
+        let mut grid = grid;
+        let mut unwrapped = grid.clone();
+        rule.propagate_global_constraints(&mut unwrapped).unwrap();
+        assert!(!unwrapped
+            .cell(4)
+            .unwrap()
+            .possible_values()
+            .any(|tile| tile == &floor));
+
+        grid = grid.with_wrapping(false, true);
+        let affected = rule.propagate_global_constraints(&mut grid).unwrap();
+        assert!(affected.is_empty());
+        assert!(grid
+            .cell(4)
+            .unwrap()
+            .possible_values()
+            .any(|tile| tile == &wall));
+        assert!(grid
+            .cell(4)
+            .unwrap()
+            .possible_values()
+            .any(|tile| tile == &floor));
+    }
+}
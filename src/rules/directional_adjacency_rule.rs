@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::{
+    adjacency_graph::DirectionalAdjacencyGraph,
+    direction::Direction,
+    error::WfcError,
+    grid::Grid,
+    tile_set::TileSet,
+    types::TileType,
+};
+
+use super::{RegistryCache, Rule};
+
+pub struct DirectionalAdjacencyRule<T: TileType> {
+    adjacency_graph: DirectionalAdjacencyGraph<T>,
+    masks: RegistryCache<T, HashMap<Direction, Vec<TileSet>>>,
+}
+
+impl<T: TileType> DirectionalAdjacencyRule<T> {
+    pub fn new(adjacency_graph: DirectionalAdjacencyGraph<T>) -> Self {
+        Self {
+            adjacency_graph,
+            masks: RegistryCache::new(),
+        }
+    }
+}
+
+impl<T: TileType> Rule<T> for DirectionalAdjacencyRule<T> {
+    fn propagate_constraints(
+        &self,
+        grid: &mut Grid<T>,
+        cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        let masks = self.masks.get(grid.registry(), || {
+            self.adjacency_graph.compile(grid.registry())
+        });
+        constrain_neighbors(grid, cell, &masks, "DirectionalAdjacencyRule")
+    }
+}
+
+/// Narrows every neighbor of `cell` to the union of the masks of its
+/// remaining tiles in the direction of that neighbor. Directions without
+/// masks allow nothing.
+pub(crate) fn constrain_neighbors<T: TileType>(
+    grid: &mut Grid<T>,
+    cell: usize,
+    masks: &HashMap<Direction, Vec<TileSet>>,
+    rule: &str,
+) -> Result<Vec<usize>, WfcError> {
+    let domain = grid
+        .cell(cell)
+        .ok_or(WfcError::UnknownCell { cell })?
+        .domain()
+        .clone();
+    let mut affected_cells = Vec::new();
+
+    for (direction, neighbor) in grid.get_directional_neighbors(cell) {
+        let mut allowed_neighbors = grid.registry().empty_set();
+        if let Some(direction_masks) = masks.get(&direction) {
+            for index in domain.iter() {
+                allowed_neighbors.union_with(&direction_masks[index]);
+            }
+        }
+
+        // Checked before taking the cell mutably, which journals it
+        if grid
+            .cell(neighbor)
+            .unwrap()
+            .domain()
+            .is_subset(&allowed_neighbors)
+        {
+            continue;
+        }
+        let neighbor_cell = grid.cell_mut(neighbor).unwrap();
+        if neighbor_cell.constrain(&allowed_neighbors) {
+            if neighbor_cell.is_contradiction() {
+                return Err(WfcError::Contradiction {
+                    cell: neighbor,
+                    rule: rule.to_string(),
+                });
+            }
+            affected_cells.push(neighbor);
+        }
+    }
+
+    Ok(affected_cells)
+}
@@ -1,14 +1,70 @@
+use std::{
+    cell::{Ref, RefCell},
+    sync::Arc,
+};
+
 use crate::{
+    error::WfcError,
     grid::Grid,
+    registry::TileRegistry,
     types::TileType,
 };
 pub mod adjacency_rule;
+pub mod border_rule;
+pub mod connectivity_rule;
+pub mod directional_adjacency_rule;
+pub mod socket_rule;
+pub mod tile_count_rule;
 
 pub trait Rule<T: TileType> {
     fn propagate_constraints(
         &self,
         grid: &mut Grid<T>,
-        x: usize,
-        y: usize,
-    ) -> Result<Vec<(usize, usize)>, String>;
+        cell: usize,
+    ) -> Result<Vec<usize>, WfcError>;
+
+    /// Called once the local propagation settled, for rules that need to
+    /// look at the whole grid at once. Returns the cells it narrowed, which
+    /// are propagated in turn. Only called from the first observation on,
+    /// after the presets and the layout of the grid are final. Runs after
+    /// every propagation, so a rule that walks the whole grid here slows
+    /// down large grids noticeably.
+    fn propagate_global_constraints(&self, _grid: &mut Grid<T>) -> Result<Vec<usize>, WfcError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Data a rule derives from the tile registry of a grid, like masks indexed
+/// by tile. Derived again whenever the rule runs on a grid with another
+/// registry, e.g. one swapped into [`WFC::grid`](crate::wfc::WFC::grid).
+#[derive(Debug, Clone)]
+pub(crate) struct RegistryCache<T: TileType, C> {
+    compiled: RefCell<Option<(Arc<TileRegistry<T>>, C)>>,
+}
+
+impl<T: TileType, C> RegistryCache<T, C> {
+    pub(crate) fn new() -> Self {
+        Self {
+            compiled: RefCell::new(None),
+        }
+    }
+
+    /// The data compiled for `registry`, running `compile` first unless it
+    /// was already compiled for that same registry.
+    pub(crate) fn get(
+        &self,
+        registry: &Arc<TileRegistry<T>>,
+        compile: impl FnOnce() -> C,
+    ) -> Ref<'_, C> {
+        let up_to_date = matches!(
+            &*self.compiled.borrow(),
+            Some((compiled_for, _)) if Arc::ptr_eq(compiled_for, registry)
+        );
+        if !up_to_date {
+            *self.compiled.borrow_mut() = Some((registry.clone(), compile()));
+        }
+        Ref::map(self.compiled.borrow(), |compiled| {
+            &compiled.as_ref().unwrap().1
+        })
+    }
 }
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+
+use crate::{
+    direction::Direction,
+    error::WfcError,
+    grid::Grid,
+    registry::TileRegistry,
+    symmetry::TileVariants,
+    tile_set::TileSet,
+    types::{PossibleValue, TileType},
+};
+
+use super::{directional_adjacency_rule::constrain_neighbors, RegistryCache, Rule};
+
+/// Derives which tiles fit next to each other from labels on their sides,
+/// instead of listing every pair. Two tiles fit if the sockets facing each
+/// other match:
+///
+/// ```
+/// use rusty_wave_function_collapse::{
+///     direction::Direction::*, rules::socket_rule::SocketRule, types::Tile,
+/// };
+///
+/// let straight = Tile::new('│', "straight", 1);
+/// let corner = Tile::new('└', "corner", 1);
+/// let meadow = Tile::new('.', "meadow", 1);
+///
+/// let mut rule = SocketRule::new();
+/// rule.set_sockets(&straight, &[(North, "road"), (East, "grass"), (South, "road"), (West, "grass")]);
+/// rule.set_sockets(&corner, &[(North, "road"), (East, "road"), (South, "grass"), (West, "grass")]);
+/// rule.set_all_sockets(&meadow, "grass");
+///
+/// assert!(rule.is_valid_neighbor(&straight, South, &corner));
+/// assert!(rule.is_valid_neighbor(&straight, East, &meadow));
+/// assert!(!rule.is_valid_neighbor(&corner, East, &meadow));
+/// ```
+///
+/// A label matches itself, unless it was paired with a mirrored counterpart
+/// through [`SocketRule::add_mirrored_pair`]: a "shore-left" side then only
+/// fits a "shore-right" side, for edges that aren't symmetric along the side.
+/// Sides without a socket fit anything, e.g. the `Up` and `Down` sides of
+/// tiles with only horizontal sockets.
+#[derive(Debug, Clone)]
+pub struct SocketRule<T: TileType> {
+    /// Label of every side, and whether it is read mirrored.
+    sockets: HashMap<(PossibleValue<T>, Direction), (String, bool)>,
+    mirrored: HashMap<String, String>,
+    masks: RegistryCache<T, HashMap<Direction, Vec<TileSet>>>,
+}
+
+impl<T: TileType> Default for SocketRule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TileType> SocketRule<T> {
+    pub fn new() -> Self {
+        Self {
+            sockets: HashMap::new(),
+            mirrored: HashMap::new(),
+            masks: RegistryCache::new(),
+        }
+    }
+
+    pub fn set_socket(&mut self, tile: &PossibleValue<T>, direction: Direction, label: &str) {
+        self.sockets
+            .insert((tile.clone(), direction), (label.to_string(), false));
+        self.masks = RegistryCache::new();
+    }
+
+    pub fn set_sockets(&mut self, tile: &PossibleValue<T>, sockets: &[(Direction, &str)]) {
+        for &(direction, label) in sockets {
+            self.set_socket(tile, direction, label);
+        }
+    }
+
+    /// Uses the same label on every side of `tile`, in every lattice.
+    pub fn set_all_sockets(&mut self, tile: &PossibleValue<T>, label: &str) {
+        for direction in Direction::ALL {
+            self.set_socket(tile, direction, label);
+        }
+    }
+
+    /// Sets the sockets of every variant, given those of the tile in its
+    /// original orientation. The sockets turn with the variant, and mirrored
+    /// variants get the mirrored counterpart of every label.
+    pub fn set_variant_sockets(
+        &mut self,
+        variants: &TileVariants<T>,
+        sockets: &[(Direction, &str)],
+    ) {
+        for (tile, orientation) in variants.iter() {
+            for &(direction, label) in sockets {
+                self.sockets.insert(
+                    (tile.clone(), orientation.apply(direction)),
+                    (label.to_string(), orientation.reflected),
+                );
+            }
+        }
+        self.masks = RegistryCache::new();
+    }
+
+    /// Makes `a` and `b` each other's mirrored counterpart, so an `a` side
+    /// only fits a `b` side and the other way around.
+    pub fn add_mirrored_pair(&mut self, a: &str, b: &str) {
+        self.mirrored.insert(a.to_string(), b.to_string());
+        self.mirrored.insert(b.to_string(), a.to_string());
+        self.masks = RegistryCache::new();
+    }
+
+    pub fn socket(&self, tile: &PossibleValue<T>, direction: Direction) -> Option<&str> {
+        self.sockets
+            .get(&(tile.clone(), direction))
+            .map(|(label, mirrored)| match mirrored {
+                true => self.counterpart(label),
+                false => label.as_str(),
+            })
+    }
+
+    /// The label a side must have to face a `label` side.
+    pub fn counterpart<'a>(&'a self, label: &'a str) -> &'a str {
+        self.mirrored.get(label).map_or(label, String::as_str)
+    }
+
+    /// Whether `b` may be placed in `direction` of `a`.
+    pub fn is_valid_neighbor(
+        &self,
+        a: &PossibleValue<T>,
+        direction: Direction,
+        b: &PossibleValue<T>,
+    ) -> bool {
+        match (
+            self.socket(a, direction),
+            self.socket(b, direction.opposite()),
+        ) {
+            (Some(a), Some(b)) => self.counterpart(a) == b,
+            _ => true,
+        }
+    }
+
+    /// Precompiles the sockets into one mask per direction and registered
+    /// tile, holding the indices of the tiles that fit on that side.
+    pub fn compile(&self, registry: &TileRegistry<T>) -> HashMap<Direction, Vec<TileSet>> {
+        Direction::ALL
+            .iter()
+            .map(|&direction| {
+                let masks = registry
+                    .tiles()
+                    .iter()
+                    .map(|a| {
+                        registry.to_set(
+                            registry
+                                .tiles()
+                                .iter()
+                                .filter(|b| self.is_valid_neighbor(a, direction, b)),
+                        )
+                    })
+                    .collect();
+                (direction, masks)
+            })
+            .collect()
+    }
+}
+
+impl<T: TileType> Rule<T> for SocketRule<T> {
+    fn propagate_constraints(
+        &self,
+        grid: &mut Grid<T>,
+        cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        let masks = self
+            .masks
+            .get(grid.registry(), || self.compile(grid.registry()));
+        constrain_neighbors(grid, cell, &masks, "SocketRule")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        symmetry::{Orientation, Symmetry},
+        types::Tile,
+    };
+
+    #[test]
+    fn mirrored_labels_only_fit_their_counterpart() {
+        let left = Tile::new('a', "a", 1);
+        let right = Tile::new('b', "b", 1);
+        let mut rule = SocketRule::new();
+        rule.set_socket(&left, Direction::East, "shore-left");
+        rule.set_socket(&right, Direction::West, "shore-right");
+        rule.set_socket(&right, Direction::East, "shore-left");
+        assert!(!rule.is_valid_neighbor(&left, Direction::East, &right));
+
+        rule.add_mirrored_pair("shore-left", "shore-right");
+        assert!(rule.is_valid_neighbor(&left, Direction::East, &right));
+        assert!(rule.is_valid_neighbor(&right, Direction::West, &left));
+        // The same label on both sides no longer fits
+        rule.set_socket(&left, Direction::West, "shore-left");
+        assert!(!rule.is_valid_neighbor(&right, Direction::East, &left));
+    }
+
+    #[test]
+    fn sides_without_a_socket_fit_anything() {
+        let road = Tile::new('#', "road", 1);
+        let meadow = Tile::new('.', "meadow", 1);
+        let mut rule = SocketRule::new();
+        rule.set_sockets(
+            &road,
+            &[(Direction::North, "road"), (Direction::South, "road")],
+        );
+        rule.set_all_sockets(&meadow, "grass");
+        assert!(!rule.is_valid_neighbor(&road, Direction::North, &meadow));
+        assert!(rule.is_valid_neighbor(&road, Direction::East, &meadow));
+        assert!(rule.is_valid_neighbor(&road, Direction::Up, &road));
+    }
+
+    #[test]
+    fn reflected_variants_get_mirrored_sockets() {
+        let variants = Tile::with_symmetry('F', "F", 8, Symmetry::F).unwrap();
+        let mut rule = SocketRule::new();
+        rule.add_mirrored_pair("shore-left", "shore-right");
+        rule.set_variant_sockets(
+            &variants,
+            &[
+                (Direction::North, "shore-left"),
+                (Direction::East, "east"),
+                (Direction::South, "south"),
+                (Direction::West, "west"),
+            ],
+        );
+
+        let turned = variants.get(Orientation::new(1, false));
+        assert_eq!(rule.socket(turned, Direction::East), Some("shore-left"));
+        assert_eq!(rule.socket(turned, Direction::South), Some("east"));
+
+        let reflected = variants.get(Orientation::new(0, true));
+        assert_eq!(
+            rule.socket(reflected, Direction::North),
+            Some("shore-right")
+        );
+        assert_eq!(rule.socket(reflected, Direction::East), Some("west"));
+        assert_eq!(rule.socket(reflected, Direction::West), Some("east"));
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    error::WfcError,
+    grid::Grid,
+    types::{PossibleValue, TileType},
+};
+
+use super::Rule;
+
+#[derive(Debug, Clone)]
+struct Limit<T: TileType> {
+    tile: PossibleValue<T>,
+    min: usize,
+    /// Share of the active cells, combined with `min`.
+    min_share: f64,
+    max: usize,
+}
+
+impl<T: TileType> Limit<T> {
+    fn min(&self, active_cells: usize) -> usize {
+        self.min
+            .max((self.min_share * active_cells as f64).ceil() as usize)
+    }
+}
+
+/// Limits how often tiles occur in the whole grid, e.g. exactly one castle,
+/// at most 3 lakes or at least 10% water. Only active cells are counted.
+///
+/// Once a tile reached its maximum it is removed from every other cell, and
+/// once only as many cells may still become it as its minimum requires,
+/// those cells are collapsed to it.
+#[derive(Debug, Clone)]
+pub struct TileCountRule<T: TileType> {
+    limits: Vec<Limit<T>>,
+}
+
+impl<T: TileType> Default for TileCountRule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: TileType> TileCountRule<T> {
+    pub fn new() -> Self {
+        Self { limits: Vec::new() }
+    }
+
+    fn limit_mut(&mut self, tile: &PossibleValue<T>) -> &mut Limit<T> {
+        let position = match self.limits.iter().position(|limit| &limit.tile == tile) {
+            Some(position) => position,
+            None => {
+                self.limits.push(Limit {
+                    tile: tile.clone(),
+                    min: 0,
+                    min_share: 0.0,
+                    max: usize::MAX,
+                });
+                self.limits.len() - 1
+            }
+        };
+        &mut self.limits[position]
+    }
+
+    pub fn set_min(&mut self, tile: &PossibleValue<T>, min: usize) {
+        self.limit_mut(tile).min = min;
+    }
+
+    /// Requires at least `share` of the active cells, between 0 and 1, to
+    /// be `tile`.
+    pub fn set_min_share(&mut self, tile: &PossibleValue<T>, share: f64) {
+        self.limit_mut(tile).min_share = share;
+    }
+
+    pub fn set_max(&mut self, tile: &PossibleValue<T>, max: usize) {
+        self.limit_mut(tile).max = max;
+    }
+
+    pub fn set_exactly(&mut self, tile: &PossibleValue<T>, count: usize) {
+        self.set_min(tile, count);
+        self.set_max(tile, count);
+    }
+}
+
+impl<T: TileType> Rule<T> for TileCountRule<T> {
+    fn propagate_constraints(
+        &self,
+        _grid: &mut Grid<T>,
+        _cell: usize,
+    ) -> Result<Vec<usize>, WfcError> {
+        Ok(Vec::new())
+    }
+
+    fn propagate_global_constraints(&self, grid: &mut Grid<T>) -> Result<Vec<usize>, WfcError> {
+        let indices = self
+            .limits
+            .iter()
+            .map(|limit| {
+                grid.registry()
+                    .index_of(&limit.tile)
+                    .ok_or_else(|| WfcError::UnknownTile(limit.tile.name.clone()))
+            })
+            .collect::<Result<Vec<usize>, WfcError>>()?;
+        // Collapsed and open cells that may become each tile, counted in one
+        // pass over the grid
+        let mut collapsed = vec![Vec::new(); self.limits.len()];
+        let mut open = vec![Vec::new(); self.limits.len()];
+        let mut active_cells = 0;
+        for cell in (0..grid.len()).filter(|&cell| grid.is_active(cell)) {
+            active_cells += 1;
+            let domain = grid.cell(cell).unwrap().domain();
+            let is_collapsed = domain.single().is_some();
+            for (i, &index) in indices.iter().enumerate() {
+                if domain.contains(index) {
+                    match is_collapsed {
+                        true => collapsed[i].push(cell),
+                        false => open[i].push(cell),
+                    }
+                }
+            }
+        }
+
+        for (i, limit) in self.limits.iter().enumerate() {
+            let (collapsed, open) = (&collapsed[i], &open[i]);
+            let rule = || format!("TileCountRule for {}", limit.tile.name);
+
+            let min = limit.min(active_cells);
+            if collapsed.len() > limit.max {
+                return Err(WfcError::Contradiction {
+                    cell: *collapsed.last().unwrap(),
+                    rule: rule(),
+                });
+            } else if collapsed.len() + open.len() < min {
+                // Possibly no cell is left that may become the tile
+                return Err(WfcError::Unsatisfiable { rule: rule() });
+            } else if open.is_empty() {
+                continue;
+            }
+
+            // The counts of the other tiles are outdated after any change,
+            // they are checked again once it was propagated
+            if collapsed.len() == limit.max {
+                // Open cells have other tiles left, banning can't empty them
+                for &cell in open {
+                    grid.cell_mut(cell).unwrap().ban(&limit.tile);
+                }
+                return Ok(open.clone());
+            } else if collapsed.len() + open.len() == min {
+                for &cell in open {
+                    grid.cell_mut(cell).unwrap().set(&limit.tile)?;
+                }
+                return Ok(open.clone());
+            }
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::tests::water_and_land, types::Tile};
+
+    #[test]
+    fn bans_a_tile_that_reached_its_maximum() {
+        let (mut grid, water, land) = water_and_land(2, 2);
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        let mut rule = TileCountRule::new();
+        rule.set_max(&water, 1);
+        let affected = rule.propagate_global_constraints(&mut grid).unwrap();
+        assert_eq!(affected, vec![1, 2, 3]);
+        for cell in affected {
+            let tiles: Vec<_> = grid.cell(cell).unwrap().possible_values().collect();
+            assert_eq!(tiles, vec![&land]);
+        }
+    }
+
+    #[test]
+    fn reports_a_minimum_no_cell_can_reach() {
+        let (mut grid, water, land) = water_and_land(2, 2);
+        for cell in 0..4 {
+            grid.cell_mut(cell).unwrap().set(&land).unwrap();
+        }
+        let mut rule = TileCountRule::new();
+        rule.set_min(&water, 1);
+        assert!(matches!(
+            rule.propagate_global_constraints(&mut grid),
+            Err(WfcError::Unsatisfiable { .. })
+        ));
+    }
+
+    #[test]
+    fn reports_unregistered_tiles() {
+        let (mut grid, _, _) = water_and_land(2, 2);
+        let mut rule = TileCountRule::new();
+        rule.set_max(&Tile::new('C', "Castle", 1), 1);
+        assert_eq!(
+            rule.propagate_global_constraints(&mut grid),
+            Err(WfcError::UnknownTile("Castle".to_string()))
+        );
+    }
+}
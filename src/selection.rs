@@ -0,0 +1,239 @@
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, RngCore};
+
+use crate::{entropy::EntropyQueue, grid::Grid, types::TileType};
+
+/// Chooses the cell the solver observes next.
+///
+/// Built in are the lowest Shannon entropy ([`EntropyQueue::new`], the
+/// default), the fewest remaining tiles ([`Measure::RemainingValues`]),
+/// [`Scanline`] and [`RandomOrder`] order, and [`GrowFromCollapsed`], which
+/// grows the solved area outwards from the presets.
+///
+/// [`Measure::RemainingValues`]: crate::entropy::Measure::RemainingValues
+pub trait CellSelector<T: TileType> {
+    /// The next cell to observe, `None` once every active cell is collapsed.
+    fn select(&mut self, grid: &Grid<T>, rng: &mut dyn RngCore) -> Option<usize>;
+
+    /// Called whenever the possible values of `cell` changed, including when
+    /// it was collapsed or restored by backtracking.
+    fn cell_changed(&mut self, _grid: &Grid<T>, _cell: usize, _rng: &mut dyn RngCore) {}
+}
+
+/// Whether `cell` still has to be observed.
+pub(crate) fn is_candidate<T: TileType>(grid: &Grid<T>, cell: usize) -> bool {
+    grid.cell(cell).is_some_and(|cell| !cell.is_collapsed()) && grid.is_active(cell)
+}
+
+impl<T: TileType> CellSelector<T> for EntropyQueue {
+    fn select(&mut self, grid: &Grid<T>, rng: &mut dyn RngCore) -> Option<usize> {
+        self.lowest(grid, rng)
+    }
+
+    fn cell_changed(&mut self, grid: &Grid<T>, cell: usize, rng: &mut dyn RngCore) {
+        self.update(grid, cell, rng);
+    }
+}
+
+/// Observes the cells one after the other in index order, row by row.
+#[derive(Debug, Clone, Default)]
+pub struct Scanline {
+    next: usize,
+}
+
+impl Scanline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: TileType> CellSelector<T> for Scanline {
+    fn select(&mut self, grid: &Grid<T>, _rng: &mut dyn RngCore) -> Option<usize> {
+        while self.next < grid.len() && !is_candidate(grid, self.next) {
+            self.next += 1;
+        }
+        (self.next < grid.len()).then_some(self.next)
+    }
+
+    fn cell_changed(&mut self, grid: &Grid<T>, cell: usize, _rng: &mut dyn RngCore) {
+        // Backtracking can reopen cells that were already passed
+        if is_candidate(grid, cell) {
+            self.next = self.next.min(cell);
+        }
+    }
+}
+
+/// Observes the cells in a random order, shuffled with the solver's random
+/// number generator.
+#[derive(Debug, Clone, Default)]
+pub struct RandomOrder {
+    order: Vec<usize>,
+    /// Position of every cell in `order`.
+    positions: Vec<usize>,
+    next: usize,
+}
+
+impl RandomOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T: TileType> CellSelector<T> for RandomOrder {
+    fn select(&mut self, grid: &Grid<T>, rng: &mut dyn RngCore) -> Option<usize> {
+        if self.order.len() != grid.len() {
+            self.order = (0..grid.len()).collect();
+            self.order.shuffle(rng);
+            self.positions = vec![0; grid.len()];
+            for (position, &cell) in self.order.iter().enumerate() {
+                self.positions[cell] = position;
+            }
+            self.next = 0;
+        }
+        while self.next < self.order.len() && !is_candidate(grid, self.order[self.next]) {
+            self.next += 1;
+        }
+        self.order.get(self.next).copied()
+    }
+
+    fn cell_changed(&mut self, grid: &Grid<T>, cell: usize, _rng: &mut dyn RngCore) {
+        if let Some(&position) = self.positions.get(cell) {
+            if is_candidate(grid, cell) {
+                self.next = self.next.min(position);
+            }
+        }
+    }
+}
+
+/// Observes the cells next to already collapsed ones first, so the solved
+/// area grows outwards from the presets in rings. Without any collapsed cell
+/// to grow from, the first remaining cell in index order is observed.
+#[derive(Debug, Clone, Default)]
+pub struct GrowFromCollapsed {
+    frontier: VecDeque<usize>,
+    started: bool,
+}
+
+impl GrowFromCollapsed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn has_collapsed_neighbor<T: TileType>(grid: &Grid<T>, cell: usize) -> bool {
+        grid.get_adjacent_cells(cell)
+            .iter()
+            .any(|neighbor| neighbor.is_collapsed())
+    }
+}
+
+impl<T: TileType> CellSelector<T> for GrowFromCollapsed {
+    fn select(&mut self, grid: &Grid<T>, _rng: &mut dyn RngCore) -> Option<usize> {
+        if !self.started {
+            self.started = true;
+            self.frontier = (0..grid.len())
+                .filter(|&cell| {
+                    is_candidate(grid, cell) && Self::has_collapsed_neighbor(grid, cell)
+                })
+                .collect();
+        }
+        while let Some(&cell) = self.frontier.front() {
+            if is_candidate(grid, cell) {
+                return Some(cell);
+            }
+            self.frontier.pop_front();
+        }
+        // Nothing to grow from, start a new region
+        (0..grid.len()).find(|&cell| is_candidate(grid, cell))
+    }
+
+    fn cell_changed(&mut self, grid: &Grid<T>, cell: usize, _rng: &mut dyn RngCore) {
+        if !self.started {
+            return;
+        }
+        if grid.cell(cell).is_some_and(|cell| cell.is_collapsed()) {
+            let neighbors = grid.get_neighbors(cell);
+            self.frontier.extend(
+                neighbors
+                    .into_iter()
+                    .filter(|&neighbor| is_candidate(grid, neighbor)),
+            );
+        } else if is_candidate(grid, cell) && Self::has_collapsed_neighbor(grid, cell) {
+            // Reopened by backtracking
+            self.frontier.push_back(cell);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::grid::tests::water_and_land;
+
+    /// Collapses every cell left in the order `selector` picks them.
+    fn pick_all(
+        grid: &mut Grid<char>,
+        selector: &mut impl CellSelector<char>,
+        rng: &mut StdRng,
+    ) -> Vec<usize> {
+        let water = grid.registry().find_by_name("Water").unwrap().clone();
+        let mut picked = Vec::new();
+        while let Some(cell) = selector.select(grid, rng) {
+            grid.cell_mut(cell).unwrap().set(&water).unwrap();
+            selector.cell_changed(grid, cell, rng);
+            picked.push(cell);
+        }
+        picked
+    }
+
+    /// Collapses every cell of a 3 × 3 grid, then undoes the last `reopened`
+    /// ones like backtracking would. Returns the cells picked before and
+    /// after.
+    fn pick_again_after_undo(
+        mut selector: impl CellSelector<char>,
+        reopened: usize,
+    ) -> (Vec<usize>, Vec<usize>) {
+        let (mut grid, _, _) = water_and_land(3, 3);
+        grid.set_journaling(true);
+        let mut rng = StdRng::seed_from_u64(0);
+        let first = pick_all(&mut grid, &mut selector, &mut rng);
+        for cell in grid.undo(grid.len() - reopened) {
+            selector.cell_changed(&grid, cell, &mut rng);
+        }
+        let again = pick_all(&mut grid, &mut selector, &mut rng);
+        (first, again)
+    }
+
+    fn assert_picks_reopened_cells(selector: impl CellSelector<char>) {
+        let (first, mut again) = pick_again_after_undo(selector, 4);
+        let mut all = first.clone();
+        all.sort_unstable();
+        assert_eq!(all, (0..9).collect::<Vec<_>>());
+        let mut reopened = first[5..].to_vec();
+        reopened.sort_unstable();
+        again.sort_unstable();
+        assert_eq!(again, reopened);
+    }
+
+    #[test]
+    fn scanline_goes_back_to_reopened_cells() {
+        let (first, again) = pick_again_after_undo(Scanline::new(), 4);
+        assert_eq!(first, (0..9).collect::<Vec<_>>());
+        assert_eq!(again, vec![5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn random_order_goes_back_to_reopened_cells() {
+        assert_picks_reopened_cells(RandomOrder::new());
+    }
+
+    #[test]
+    fn growth_goes_back_to_reopened_cells() {
+        let (first, _) = pick_again_after_undo(GrowFromCollapsed::new(), 4);
+        // Rings around the first cell
+        assert_eq!(&first[..3], &[0, 1, 3]);
+        assert_picks_reopened_cells(GrowFromCollapsed::new());
+    }
+}
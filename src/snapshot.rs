@@ -0,0 +1,213 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::WfcError,
+    grid::{Cell, Grid},
+    topology::{Lattice, LatticeTopology},
+    types::{PossibleValues, TileType},
+};
+
+/// The state of a possibly partially solved grid: its shape and the names of
+/// the tiles each cell may still become. Snapshots can be saved as JSON to
+/// checkpoint a long generation, share it in a bug report or diff two runs.
+///
+/// Only the grid is saved, a solver resumed with [`WFC::from_grid`] starts
+/// without backtracking history.
+///
+/// [`WFC::from_grid`]: crate::wfc::WFC::from_grid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GridSnapshot {
+    /// `None` for topologies other than lattices, which can only be restored
+    /// with [`Grid::load_snapshot`].
+    pub lattice: Option<Lattice>,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    #[serde(default)]
+    pub wrap_x: bool,
+    #[serde(default)]
+    pub wrap_y: bool,
+    /// Indices of the inactive cells of a masked grid.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inactive: Vec<usize>,
+    /// Whether the inactive cells are hidden from their neighbors.
+    #[serde(default)]
+    pub hide_inactive: bool,
+    /// Names of the remaining tiles of every cell, in index order.
+    pub cells: Vec<Vec<String>>,
+}
+
+impl GridSnapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, WfcError> {
+        serde_json::from_str(text).map_err(|err| WfcError::InvalidSnapshot(err.to_string()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WfcError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_json())
+            .map_err(|err| WfcError::InvalidSnapshot(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WfcError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| WfcError::InvalidSnapshot(format!("{}: {}", path.display(), err)))?;
+        Self::from_json(&text)
+    }
+}
+
+impl<T: TileType> Grid<T> {
+    pub fn snapshot(&self) -> GridSnapshot {
+        let lattice = self.lattice();
+        GridSnapshot {
+            lattice: lattice.map(|topology| topology.lattice),
+            width: self.width(),
+            height: self.height(),
+            depth: self.depth(),
+            wrap_x: lattice.is_some_and(|topology| topology.wrap_x),
+            wrap_y: lattice.is_some_and(|topology| topology.wrap_y),
+            inactive: (0..self.len())
+                .filter(|&index| !self.is_active(index))
+                .collect(),
+            hide_inactive: self.hides_inactive(),
+            cells: self
+                .cells()
+                .iter()
+                .map(|cell| {
+                    cell.possible_values()
+                        .map(|tile| tile.name.clone())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Rebuilds a lattice grid from a snapshot, looking up the saved tile
+    /// names among `possible_tiles`.
+    pub fn from_snapshot(
+        snapshot: &GridSnapshot,
+        possible_tiles: PossibleValues<T>,
+    ) -> Result<Self, WfcError> {
+        let Some(lattice) = snapshot.lattice else {
+            return Err(WfcError::InvalidSnapshot(
+                "the grid is not a lattice, load the snapshot into a grid of the same topology"
+                    .to_string(),
+            ));
+        };
+        let mut topology =
+            LatticeTopology::new(lattice, snapshot.width, snapshot.height, snapshot.depth);
+        topology.wrap_x = snapshot.wrap_x;
+        topology.wrap_y = snapshot.wrap_y;
+        let mut grid = Self::from_topology(topology, possible_tiles);
+        grid.load_snapshot(snapshot)?;
+        Ok(grid)
+    }
+
+    /// Overwrites the cells of this grid with the saved ones. The grid must
+    /// have as many cells as the snapshot and know every saved tile name.
+    pub fn load_snapshot(&mut self, snapshot: &GridSnapshot) -> Result<(), WfcError> {
+        if snapshot.cells.len() != self.len() {
+            return Err(WfcError::InvalidSnapshot(format!(
+                "expected {} cells, found {}",
+                self.len(),
+                snapshot.cells.len()
+            )));
+        }
+
+        let registry = self.registry().clone();
+        for (index, names) in snapshot.cells.iter().enumerate() {
+            let tiles = names
+                .iter()
+                .map(|name| {
+                    registry
+                        .find_by_name(name)
+                        .ok_or_else(|| WfcError::UnknownTile(name.clone()))
+                })
+                .collect::<Result<Vec<_>, WfcError>>()?;
+            *self.cell_mut(index).unwrap() = Cell::new(registry.clone(), registry.to_set(tiles));
+        }
+
+        if !snapshot.inactive.is_empty() {
+            let mut active = vec![true; self.len()];
+            for &index in &snapshot.inactive {
+                *active
+                    .get_mut(index)
+                    .ok_or(WfcError::UnknownCell { cell: index })? = false;
+            }
+            self.restore_mask(&active, snapshot.hide_inactive);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{tests::water_and_land, InactiveCells};
+
+    fn round_trip(grid: &Grid<char>, tiles: PossibleValues<char>) -> Grid<char> {
+        let json = grid.snapshot().to_json();
+        let snapshot = GridSnapshot::from_json(&json).unwrap();
+        Grid::from_snapshot(&snapshot, tiles).unwrap()
+    }
+
+    fn tiles_of(grid: &Grid<char>) -> PossibleValues<char> {
+        grid.registry().tiles().iter().cloned().collect()
+    }
+
+    #[test]
+    fn restores_a_wrapped_grid() {
+        let (grid, water, land) = water_and_land(3, 4);
+        let mut grid = grid.with_wrapping(true, false);
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+        grid.cell_mut(5).unwrap().ban(&land);
+
+        let restored = round_trip(&grid, tiles_of(&grid));
+        assert_eq!(restored.snapshot(), grid.snapshot());
+        assert_eq!(restored.lattice(), grid.lattice());
+        for cell in 0..grid.len() {
+            assert_eq!(restored.get_neighbors(cell), grid.get_neighbors(cell));
+        }
+    }
+
+    #[test]
+    fn restores_a_masked_grid() {
+        let (mut grid, water, _) = water_and_land(3, 3);
+        grid.apply_mask(|x, y, _| (x, y) != (1, 1), &InactiveCells::Ignored)
+            .unwrap();
+        grid.cell_mut(0).unwrap().set(&water).unwrap();
+
+        let restored = round_trip(&grid, tiles_of(&grid));
+        assert_eq!(restored.snapshot(), grid.snapshot());
+        assert!(!restored.is_active(4));
+        for cell in 0..grid.len() {
+            assert_eq!(restored.get_neighbors(cell), grid.get_neighbors(cell));
+        }
+    }
+
+    #[test]
+    fn rejects_a_snapshot_of_another_size() {
+        let (grid, _, _) = water_and_land(2, 2);
+        let (mut other, _, _) = water_and_land(2, 3);
+        assert!(matches!(
+            other.load_snapshot(&grid.snapshot()),
+            Err(WfcError::InvalidSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_tile_names() {
+        let (grid, water, _) = water_and_land(2, 2);
+        let only_water = [water].into_iter().collect();
+        assert_eq!(
+            Grid::from_snapshot(&grid.snapshot(), only_water).unwrap_err(),
+            WfcError::UnknownTile("Land".to_string())
+        );
+    }
+}
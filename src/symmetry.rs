@@ -0,0 +1,295 @@
+use crate::{
+    direction::Direction,
+    error::WfcError,
+    types::{PossibleValue, Tile, TileType},
+};
+
+/// One of the eight ways to turn or flip a square tile: first mirrored
+/// left to right if `reflected`, then rotated clockwise by `rotation`
+/// quarter turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Orientation {
+    pub rotation: u8,
+    pub reflected: bool,
+}
+
+impl Orientation {
+    pub const IDENTITY: Orientation = Orientation::new(0, false);
+
+    pub const fn new(rotation: u8, reflected: bool) -> Self {
+        Self {
+            rotation: rotation % 4,
+            reflected,
+        }
+    }
+
+    pub fn all() -> [Orientation; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| Orientation::new(i % 4, i >= 4))
+    }
+
+    /// The side a side facing `direction` ends up on. `Up` and `Down` stay
+    /// in place, the diagonals turn along with the cardinal directions.
+    pub fn apply(&self, direction: Direction) -> Direction {
+        let mut direction = if self.reflected {
+            mirror(direction)
+        } else {
+            direction
+        };
+        for _ in 0..self.rotation {
+            direction = rotate_clockwise(direction);
+        }
+        direction
+    }
+
+    /// This orientation followed by `other`.
+    pub fn then(&self, other: Orientation) -> Orientation {
+        // North and East tell all eight apart
+        let (north, east) = (
+            other.apply(self.apply(Direction::North)),
+            other.apply(self.apply(Direction::East)),
+        );
+        Orientation::all()
+            .into_iter()
+            .find(|orientation| {
+                orientation.apply(Direction::North) == north
+                    && orientation.apply(Direction::East) == east
+            })
+            .unwrap()
+    }
+}
+
+fn mirror(direction: Direction) -> Direction {
+    match direction {
+        Direction::East => Direction::West,
+        Direction::West => Direction::East,
+        Direction::NorthEast => Direction::NorthWest,
+        Direction::NorthWest => Direction::NorthEast,
+        Direction::SouthEast => Direction::SouthWest,
+        Direction::SouthWest => Direction::SouthEast,
+        other => other,
+    }
+}
+
+fn rotate_clockwise(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North,
+        Direction::NorthEast => Direction::SouthEast,
+        Direction::SouthEast => Direction::SouthWest,
+        Direction::SouthWest => Direction::NorthWest,
+        Direction::NorthWest => Direction::NorthEast,
+        other => other,
+    }
+}
+
+/// How a square tile looks the same when turned or flipped, named after the
+/// letter with the same symmetries as in the original WFC. It decides how
+/// many distinct variants [`Tile::with_symmetry`] creates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    /// Looks the same in every orientation, 1 variant.
+    X,
+    /// A straight line from north to south, 2 variants.
+    I,
+    /// Connects north and east, symmetric along that diagonal, 4 variants.
+    L,
+    /// Looks the same mirrored left to right, like a junction of the west,
+    /// east and south sides, 4 variants.
+    T,
+    /// A diagonal from north-west to south-east, 2 variants.
+    Backslash,
+    /// No symmetry at all, 8 variants.
+    F,
+}
+
+impl Symmetry {
+    /// The orientations that leave the tile unchanged.
+    fn invariant(&self) -> Vec<Orientation> {
+        let orientations: &[(u8, bool)] = match self {
+            Symmetry::X => return Orientation::all().to_vec(),
+            Symmetry::I => &[(0, false), (2, false), (0, true), (2, true)],
+            Symmetry::L => &[(0, false), (1, true)],
+            Symmetry::T => &[(0, false), (0, true)],
+            Symmetry::Backslash => &[(0, false), (2, false), (1, true), (3, true)],
+            Symmetry::F => &[(0, false)],
+        };
+        orientations
+            .iter()
+            .map(|&(rotation, reflected)| Orientation::new(rotation, reflected))
+            .collect()
+    }
+
+    /// One orientation per distinct variant, starting with the identity.
+    pub fn orientations(&self) -> Vec<Orientation> {
+        let mut orientations: Vec<Orientation> = Vec::new();
+        for orientation in Orientation::all() {
+            if !orientations
+                .iter()
+                .any(|&known| self.same_variant(known, orientation))
+            {
+                orientations.push(orientation);
+            }
+        }
+        orientations
+    }
+
+    /// Whether the tile looks the same turned by `a` and by `b`.
+    fn same_variant(&self, a: Orientation, b: Orientation) -> bool {
+        self.invariant()
+            .into_iter()
+            .any(|invariant| invariant.then(a) == b)
+    }
+
+    /// Index into [`Symmetry::orientations`] of the variant that looks like
+    /// the tile turned by `orientation`.
+    pub fn variant_of(&self, orientation: Orientation) -> usize {
+        self.orientations()
+            .into_iter()
+            .position(|variant| self.same_variant(variant, orientation))
+            .unwrap()
+    }
+}
+
+/// The turned and flipped variants of one tile, see [`Tile::with_symmetry`].
+#[derive(Debug, Clone)]
+pub struct TileVariants<T: TileType> {
+    pub symmetry: Symmetry,
+    /// One tile per entry of [`Symmetry::orientations`], in the same order.
+    pub tiles: Vec<PossibleValue<T>>,
+}
+
+impl<T: TileType> TileVariants<T> {
+    /// The variant that looks like the original tile turned by `orientation`.
+    pub fn get(&self, orientation: Orientation) -> &PossibleValue<T> {
+        &self.tiles[self.symmetry.variant_of(orientation)]
+    }
+
+    /// Every variant with the orientation it was created with.
+    pub fn iter(&self) -> impl Iterator<Item = (&PossibleValue<T>, Orientation)> + '_ {
+        self.tiles.iter().zip(self.symmetry.orientations())
+    }
+}
+
+impl<T: TileType> Tile<T> {
+    /// Creates every distinct turned and flipped variant of a tile drawn in
+    /// one orientation. They share `id` and split `weight` among them, so
+    /// `weight` must be at least the number of variants. Variants are named
+    /// `name 0`, `name 1` and so on, unless there is only one.
+    pub fn with_symmetry(
+        id: T,
+        name: &str,
+        weight: i32,
+        symmetry: Symmetry,
+    ) -> Result<TileVariants<T>, WfcError> {
+        Self::with_symmetry_by(id, name, weight, symmetry, |id, _| id.clone())
+    }
+
+    /// Like [`Tile::with_symmetry`], with the id of each variant derived from
+    /// the original one, e.g. to draw a turned road with another character.
+    pub fn with_symmetry_by(
+        id: T,
+        name: &str,
+        weight: i32,
+        symmetry: Symmetry,
+        id_of: impl Fn(&T, Orientation) -> T,
+    ) -> Result<TileVariants<T>, WfcError> {
+        let orientations = symmetry.orientations();
+        let count = orientations.len() as i32;
+        if weight < count {
+            return Err(WfcError::InvalidWeights(format!(
+                "weight {} of {} can't be split among {} variants",
+                weight, name, count
+            )));
+        }
+        let tiles = orientations
+            .iter()
+            .enumerate()
+            .map(|(i, &orientation)| {
+                let name = if count == 1 {
+                    name.to_string()
+                } else {
+                    format!("{} {}", name, i)
+                };
+                let remainder = (i as i32) < weight % count;
+                let weight = weight / count + remainder as i32;
+                Tile::new(id_of(&id, orientation), &name, weight)
+            })
+            .collect();
+        Ok(TileVariants { symmetry, tiles })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn then_composes_orientations() {
+        let quarter = Orientation::new(1, false);
+        let mirrored = Orientation::new(0, true);
+        assert_eq!(quarter.then(quarter), Orientation::new(2, false));
+        assert_eq!(
+            quarter.then(Orientation::new(3, false)),
+            Orientation::IDENTITY
+        );
+        assert_eq!(mirrored.then(mirrored), Orientation::IDENTITY);
+        // Mirroring after a turn is the mirrored turn the other way
+        assert_eq!(quarter.then(mirrored), Orientation::new(3, true));
+        for a in Orientation::all() {
+            for b in Orientation::all() {
+                let composed = a.then(b);
+                for direction in Direction::ALL {
+                    assert_eq!(composed.apply(direction), b.apply(a.apply(direction)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn variant_counts_follow_the_symmetry() {
+        let counts = [
+            (Symmetry::X, 1),
+            (Symmetry::I, 2),
+            (Symmetry::L, 4),
+            (Symmetry::T, 4),
+            (Symmetry::Backslash, 2),
+            (Symmetry::F, 8),
+        ];
+        for (symmetry, count) in counts {
+            assert_eq!(symmetry.orientations().len(), count, "{:?}", symmetry);
+            assert_eq!(symmetry.orientations()[0], Orientation::IDENTITY);
+            // Every invariant orientation maps the tile onto itself
+            for invariant in symmetry.invariant() {
+                assert_eq!(symmetry.variant_of(invariant), 0, "{:?}", symmetry);
+            }
+        }
+    }
+
+    #[test]
+    fn variants_are_found_by_their_orientation() {
+        let variants = Tile::with_symmetry('┐', "Corner", 8, Symmetry::L).unwrap();
+        assert_eq!(variants.tiles.len(), 4);
+        for (tile, orientation) in variants.iter() {
+            assert_eq!(variants.get(orientation), tile);
+            assert_eq!(tile.weight, 2);
+        }
+        // Turned a full circle, or mirrored along its diagonal
+        assert_eq!(variants.get(Orientation::new(4, false)), &variants.tiles[0]);
+        assert_eq!(variants.get(Orientation::new(1, true)), &variants.tiles[0]);
+    }
+
+    #[test]
+    fn variants_split_the_weight() {
+        let variants = Tile::with_symmetry('F', "F", 11, Symmetry::F).unwrap();
+        let weights: Vec<i32> = variants.tiles.iter().map(|tile| tile.weight).collect();
+        assert_eq!(weights, vec![2, 2, 2, 1, 1, 1, 1, 1]);
+        assert!(matches!(
+            Tile::with_symmetry('F', "F", 7, Symmetry::F),
+            Err(WfcError::InvalidWeights(_))
+        ));
+        let single = Tile::with_symmetry('X', "X", 1, Symmetry::X).unwrap();
+        assert_eq!(single.tiles[0].weight, 1);
+    }
+}
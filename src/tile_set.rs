@@ -0,0 +1,194 @@
+/// Fixed-size bitset of tile indices into a [`TileRegistry`](crate::registry::TileRegistry).
+///
+/// Sets that are combined must come from the same registry, so they have the
+/// same number of words.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TileSet {
+    words: Box<[u64]>,
+}
+
+impl TileSet {
+    /// Empty set able to hold indices `0..capacity`.
+    pub fn empty(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(64)].into_boxed_slice(),
+        }
+    }
+
+    /// Set containing every index in `0..capacity`.
+    pub fn full(capacity: usize) -> Self {
+        let mut set = Self::empty(capacity);
+        for (i, word) in set.words.iter_mut().enumerate() {
+            let remaining = capacity - i * 64;
+            *word = if remaining >= 64 {
+                u64::MAX
+            } else {
+                (1 << remaining) - 1
+            };
+        }
+        set
+    }
+
+    pub fn insert(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        let added = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        added
+    }
+
+    pub fn remove(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        let removed = self.words[word] & bit != 0;
+        self.words[word] &= !bit;
+        removed
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// The only index in the set, `None` if it holds zero or several.
+    pub fn single(&self) -> Option<usize> {
+        let mut found = None;
+        for (i, &word) in self.words.iter().enumerate() {
+            if word == 0 {
+                continue;
+            }
+            if found.is_some() || word.count_ones() != 1 {
+                return None;
+            }
+            found = Some(i * 64 + word.trailing_zeros() as usize);
+        }
+        found
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+
+    /// Keeps only the indices also in `other`, returns true if any were removed.
+    pub fn intersect_with(&mut self, other: &TileSet) -> bool {
+        self.intersect_with_each_removed(other, |_| {})
+    }
+
+    /// Like [`TileSet::intersect_with`], calling `removed` with every index
+    /// that is dropped.
+    pub fn intersect_with_each_removed(
+        &mut self,
+        other: &TileSet,
+        mut removed: impl FnMut(usize),
+    ) -> bool {
+        let mut changed = false;
+        for (i, (word, &other_word)) in self.words.iter_mut().zip(other.words.iter()).enumerate() {
+            let narrowed = *word & other_word;
+            if narrowed != *word {
+                word_indices(i, *word & !narrowed).for_each(&mut removed);
+                changed = true;
+            }
+            *word = narrowed;
+        }
+        changed
+    }
+
+    /// Whether every index in the set is also in `other`.
+    pub fn is_subset(&self, other: &TileSet) -> bool {
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .all(|(&word, &other_word)| word & !other_word == 0)
+    }
+
+    /// Whether the sets have any index in common.
+    pub fn intersects(&self, other: &TileSet) -> bool {
+        self.words
+            .iter()
+            .zip(other.words.iter())
+            .any(|(&word, &other_word)| word & other_word != 0)
+    }
+
+    pub fn union_with(&mut self, other: &TileSet) {
+        for (word, &other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other_word;
+        }
+    }
+
+    /// Indices in the set, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &word)| word_indices(i, word))
+    }
+}
+
+/// Indices of the bits set in `word`, the `i`th word of a set.
+fn word_indices(i: usize, word: u64) -> impl Iterator<Item = usize> {
+    let mut remaining = word;
+    std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+        let bit = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+        Some(i * 64 + bit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_sets_end_at_their_capacity() {
+        let set = TileSet::full(64);
+        assert_eq!(set.len(), 64);
+        assert!(set.contains(63));
+        assert!(!set.contains(64));
+
+        let set = TileSet::full(65);
+        assert_eq!(set.len(), 65);
+        assert!(set.contains(64));
+        assert!(!set.contains(65));
+        assert_eq!(set.iter().last(), Some(64));
+
+        assert!(TileSet::full(0).is_empty());
+    }
+
+    #[test]
+    fn sets_combine_across_words() {
+        let mut low = TileSet::empty(130);
+        low.insert(3);
+        let mut high = TileSet::empty(130);
+        high.insert(64);
+        high.insert(129);
+        assert_eq!(high.single(), None);
+        assert!(!low.intersects(&high));
+        assert!(!high.is_subset(&low));
+
+        let mut both = low.clone();
+        both.union_with(&high);
+        assert_eq!(both.iter().collect::<Vec<_>>(), vec![3, 64, 129]);
+        assert!(low.is_subset(&both) && high.is_subset(&both));
+        assert!(high.intersects(&both));
+
+        let mut removed = Vec::new();
+        assert!(both.intersect_with_each_removed(&high, |index| removed.push(index)));
+        assert_eq!(removed, vec![3]);
+        assert!(both.remove(129));
+        assert_eq!(both.single(), Some(64));
+        assert!(!both.intersect_with(&high));
+    }
+}
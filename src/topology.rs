@@ -0,0 +1,299 @@
+use std::{fmt::Debug, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::direction::Direction;
+
+/// Decides which cells of a grid are neighbors. Rules and the solver only see
+/// cell indices and the labeled edges returned here, so the same tiles and
+/// rules run on any topology.
+pub trait Topology: Debug + Send + Sync {
+    fn cell_count(&self) -> usize;
+
+    /// Neighbors of `cell`, each labeled with the direction it lies in.
+    fn neighbors(&self, cell: usize) -> Vec<(Direction, usize)>;
+
+    /// The lattice behind this topology, if any. Renderers use it to lay out
+    /// the cells.
+    fn as_lattice(&self) -> Option<&LatticeTopology> {
+        None
+    }
+}
+
+/// How the cells of a lattice are laid out, which decides their neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Lattice {
+    /// Four neighbors, see [`Direction::SQUARE`].
+    #[default]
+    Square,
+    /// Pointy-top hexagons in "odd-r" offset coordinates: every odd row is
+    /// shifted half a cell to the east. Six neighbors, see [`Direction::HEX`].
+    Hex,
+    /// Stacked square layers along z. Six neighbors, see [`Direction::CUBIC`].
+    Cubic,
+}
+
+impl Lattice {
+    pub fn directions(&self) -> &'static [Direction] {
+        match self {
+            Lattice::Square => &Direction::SQUARE,
+            Lattice::Hex => &Direction::HEX,
+            Lattice::Cubic => &Direction::CUBIC,
+        }
+    }
+
+    /// Row, column and layer offset of the neighbor in `direction` of a cell
+    /// in row `x`, `None` if the lattice has no such direction.
+    fn offset(&self, x: usize, direction: Direction) -> Option<(isize, isize, isize)> {
+        let odd_row = x % 2 == 1;
+        match (self, direction) {
+            (Lattice::Square | Lattice::Cubic, Direction::North) => Some((-1, 0, 0)),
+            (Lattice::Square | Lattice::Cubic, Direction::South) => Some((1, 0, 0)),
+            (_, Direction::West) => Some((0, -1, 0)),
+            (_, Direction::East) => Some((0, 1, 0)),
+            (Lattice::Hex, Direction::NorthEast) => Some((-1, odd_row as isize, 0)),
+            (Lattice::Hex, Direction::NorthWest) => Some((-1, odd_row as isize - 1, 0)),
+            (Lattice::Hex, Direction::SouthEast) => Some((1, odd_row as isize, 0)),
+            (Lattice::Hex, Direction::SouthWest) => Some((1, odd_row as isize - 1, 0)),
+            (Lattice::Cubic, Direction::Up) => Some((0, 0, 1)),
+            (Lattice::Cubic, Direction::Down) => Some((0, 0, -1)),
+            _ => None,
+        }
+    }
+}
+
+/// Moves `delta` along an axis of length `len`, wrapping around if enabled.
+fn step_axis(value: usize, delta: isize, len: usize, wrap: bool) -> Option<usize> {
+    let target = value as isize + delta;
+    if (0..len as isize).contains(&target) {
+        Some(target as usize)
+    } else if wrap && len > 1 {
+        // A wrapping axis of length 1 would make a cell its own neighbor
+        Some(target.rem_euclid(len as isize) as usize)
+    } else {
+        None
+    }
+}
+
+/// A `width` × `height` × `depth` lattice. Cells are laid out layer by layer
+/// and row by row, see [`LatticeTopology::index`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatticeTopology {
+    pub lattice: Lattice,
+    pub width: usize,
+    pub height: usize,
+    /// Number of layers along z, 1 for 2D grids.
+    pub depth: usize,
+    /// Whether the last row is a neighbor of the first one, see
+    /// [`LatticeTopology::wraps_x`].
+    pub wrap_x: bool,
+    /// Whether the last column is a neighbor of the first one.
+    pub wrap_y: bool,
+}
+
+impl LatticeTopology {
+    pub fn new(lattice: Lattice, width: usize, height: usize, depth: usize) -> Self {
+        Self {
+            lattice,
+            width,
+            height,
+            depth,
+            wrap_x: false,
+            wrap_y: false,
+        }
+    }
+
+    pub fn index(&self, x: usize, y: usize, z: usize) -> Option<usize> {
+        if x < self.width && y < self.height && z < self.depth {
+            Some((z * self.width + x) * self.height + y)
+        } else {
+            None
+        }
+    }
+
+    pub fn coordinates(&self, index: usize) -> (usize, usize, usize) {
+        let (layer, y) = (index / self.height, index % self.height);
+        (layer % self.width, y, layer / self.width)
+    }
+
+    /// Whether the rows actually wrap around. Hex lattices with an odd width
+    /// don't: the first and last row would both be shifted the same way, so
+    /// their diagonal neighbors wouldn't be each other's.
+    pub fn wraps_x(&self) -> bool {
+        self.wrap_x && (self.lattice != Lattice::Hex || self.width.is_multiple_of(2))
+    }
+
+    pub fn neighbor(&self, index: usize, direction: Direction) -> Option<usize> {
+        let (x, y, z) = self.coordinates(index);
+        let (dx, dy, dz) = self.lattice.offset(x, direction)?;
+        self.index(
+            step_axis(x, dx, self.width, self.wraps_x())?,
+            step_axis(y, dy, self.height, self.wrap_y)?,
+            step_axis(z, dz, self.depth, false)?,
+        )
+    }
+}
+
+impl Topology for LatticeTopology {
+    fn cell_count(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<(Direction, usize)> {
+        self.lattice
+            .directions()
+            .iter()
+            .filter_map(|&direction| {
+                self.neighbor(cell, direction)
+                    .map(|neighbor| (direction, neighbor))
+            })
+            .collect()
+    }
+
+    fn as_lattice(&self) -> Option<&LatticeTopology> {
+        Some(self)
+    }
+}
+
+/// Arbitrary graph of nodes connected by labeled edges, for irregular meshes
+/// like Voronoi regions, room graphs or road networks. Node `n` is cell `n`.
+#[derive(Debug, Clone, Default)]
+pub struct GraphTopology {
+    edges: Vec<Vec<(Direction, usize)>>,
+}
+
+impl GraphTopology {
+    pub fn new(node_count: usize) -> Self {
+        Self {
+            edges: vec![Vec::new(); node_count],
+        }
+    }
+
+    /// Adds a node without edges and returns its index.
+    pub fn add_node(&mut self) -> usize {
+        self.edges.push(Vec::new());
+        self.edges.len() - 1
+    }
+
+    /// Connects `b` in `direction` of `a`, and `a` in the opposite direction
+    /// of `b`. Missing nodes are added.
+    pub fn add_edge(&mut self, a: usize, direction: Direction, b: usize) {
+        let needed = a.max(b) + 1;
+        if self.edges.len() < needed {
+            self.edges.resize(needed, Vec::new());
+        }
+        self.edges[a].push((direction, b));
+        self.edges[b].push((direction.opposite(), a));
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.edges.len()
+    }
+}
+
+impl Topology for GraphTopology {
+    fn cell_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<(Direction, usize)> {
+        self.edges.get(cell).cloned().unwrap_or_default()
+    }
+}
+
+/// Hides inactive cells of another topology: they have no neighbors and are
+/// nobody's neighbor, see [`Grid::apply_mask`].
+///
+/// [`Grid::apply_mask`]: crate::grid::Grid::apply_mask
+#[derive(Debug, Clone)]
+pub struct MaskedTopology {
+    inner: Arc<dyn Topology>,
+    active: Arc<[bool]>,
+}
+
+impl MaskedTopology {
+    /// `active[cell]` tells whether `cell` of `inner` takes part.
+    pub fn new(inner: Arc<dyn Topology>, active: Arc<[bool]>) -> Self {
+        Self { inner, active }
+    }
+
+    fn is_active(&self, cell: usize) -> bool {
+        self.active.get(cell).copied().unwrap_or(true)
+    }
+}
+
+impl Topology for MaskedTopology {
+    fn cell_count(&self) -> usize {
+        self.inner.cell_count()
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<(Direction, usize)> {
+        if !self.is_active(cell) {
+            return Vec::new();
+        }
+        self.inner
+            .neighbors(cell)
+            .into_iter()
+            .filter(|&(_, neighbor)| self.is_active(neighbor))
+            .collect()
+    }
+
+    fn as_lattice(&self) -> Option<&LatticeTopology> {
+        self.inner.as_lattice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that every cell is the neighbor of each of its neighbors, in
+    /// the opposite direction.
+    fn assert_mutual(topology: &LatticeTopology) {
+        for cell in 0..topology.cell_count() {
+            for (direction, neighbor) in topology.neighbors(cell) {
+                assert!(
+                    topology
+                        .neighbors(neighbor)
+                        .contains(&(direction.opposite(), cell)),
+                    "{:?}: {} has {} in {:?} but not the other way around",
+                    topology,
+                    cell,
+                    neighbor,
+                    direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn neighbors_are_mutual() {
+        for lattice in [Lattice::Square, Lattice::Hex, Lattice::Cubic] {
+            for (width, height) in [(1, 1), (2, 3), (4, 4), (5, 5), (6, 3)] {
+                for (wrap_x, wrap_y) in [(false, false), (true, false), (false, true), (true, true)]
+                {
+                    let depth = if lattice == Lattice::Cubic { 3 } else { 1 };
+                    let mut topology = LatticeTopology::new(lattice, width, height, depth);
+                    topology.wrap_x = wrap_x;
+                    topology.wrap_y = wrap_y;
+                    assert_mutual(&topology);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn odd_hex_lattices_dont_wrap_rows() {
+        let mut topology = LatticeTopology::new(Lattice::Hex, 5, 5, 1);
+        topology.wrap_x = true;
+        assert!(!topology.wraps_x());
+        assert_eq!(topology.neighbor(0, Direction::NorthEast), None);
+
+        topology.width = 6;
+        assert!(topology.wraps_x());
+        assert_eq!(
+            topology.neighbor(0, Direction::NorthEast),
+            topology.index(5, 0, 0)
+        );
+    }
+}
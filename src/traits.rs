@@ -1,9 +1,17 @@
 use colored::Color;
 
-use crate::{grid::Grid, types::TileType};
+use crate::{error::WfcError, grid::Grid, types::TileType};
 
 pub trait Renderer<T: TileType> {
     fn render(&self, grid: &Grid<T>);
+
+    /// Called once by [`WFC::run`] after the grid was solved, for renderers
+    /// that only output the final grid.
+    ///
+    /// [`WFC::run`]: crate::wfc::WFC::run
+    fn finish(&self, _grid: &Grid<T>) -> Result<(), WfcError> {
+        Ok(())
+    }
 }
 
 pub trait AsciiRenderable {
@@ -13,3 +21,15 @@ pub trait AsciiRenderable {
 pub trait ColorRenderable {
     fn get_color(&self) -> Color;
 }
+
+impl AsciiRenderable for char {
+    fn get_ascii_representation(&self) -> char {
+        *self
+    }
+}
+
+impl ColorRenderable for char {
+    fn get_color(&self) -> Color {
+        Color::White
+    }
+}
@@ -5,6 +5,7 @@ pub type PossibleValues<T> = HashSet<PossibleValue<T>>;
 
 pub trait TileType: Eq + Hash + Clone + Debug {}
 // impl<T: Eq + Hash + Clone> TileType for T {}
+impl TileType for char {}
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Tile<T: TileType> {
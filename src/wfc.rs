@@ -1,32 +1,63 @@
-use std::usize;
+use std::collections::{HashSet, VecDeque};
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 use crate::{
-    grid::Grid,
+    entropy::{EntropyQueue, TieBreak},
+    error::WfcError,
+    grid::{Grid, InactiveCells},
     rules::Rule,
+    selection::CellSelector,
+    topology::Lattice,
     traits::Renderer,
     types::{PossibleValue, PossibleValues, TileType},
 };
 
+const DEFAULT_MAX_BACKTRACK_DEPTH: usize = 64;
+const DEFAULT_MAX_BACKTRACKS: usize = 10_000;
+
+/// A cell that was observed, remembered to roll back to the state right
+/// before it when a later propagation runs into a contradiction.
+struct DecisionPoint<T: TileType> {
+    /// Length of the grid's journal before the observation.
+    journal_len: usize,
+    cell: usize,
+    tile: PossibleValue<T>,
+}
+
+/// Outcome of a single observation made by [`WFC::step`].
+#[derive(Debug, Clone)]
+pub enum StepEvent<T: TileType> {
+    /// `cell` was collapsed to `tile` and propagation narrowed the cells in
+    /// `constrained`.
+    Collapsed {
+        cell: usize,
+        tile: PossibleValue<T>,
+        constrained: Vec<usize>,
+    },
+    /// Collapsing `cell` to `tile` caused a contradiction, the solver rolled
+    /// back to an earlier decision and banned the tile chosen there.
+    Backtracked {
+        cell: usize,
+        tile: PossibleValue<T>,
+        reason: WfcError,
+    },
+}
+
 pub struct WFC<T: TileType, R: Renderer<T>> {
     pub grid: Grid<T>,
     rules: Vec<Box<dyn Rule<T>>>,
     renderer: Option<R>,
-}
-
-// TODO move me to entropy file?
-fn calculate_shannon_entropy<T: TileType>(possible_values: &PossibleValues<T>) -> f64 {
-    let total_weight: f64 = possible_values.iter().map(|tile| tile.weight as f64).sum();
-    if total_weight == 0.0 {
-        return 0.0; // Prevent log(0) errors
-    }
-
-    possible_values
-        .iter()
-        .map(|tile| {
-            let p = (tile.weight as f64) / total_weight;
-            -p * p.log2()
-        })
-        .sum()
+    history: VecDeque<DecisionPoint<T>>,
+    max_backtrack_depth: usize,
+    /// Budget of decisions that may be undone over the whole run.
+    max_backtracks: usize,
+    backtracks: usize,
+    rng: Box<dyn RngCore>,
+    selector: Box<dyn CellSelector<T>>,
+    /// Whether the first observation started. Rules over the whole grid only
+    /// run from then on, so the layout can still change after presets.
+    initialized: bool,
 }
 
 impl<T: TileType, R: Renderer<T>> WFC<T, R> {
@@ -37,69 +68,323 @@ impl<T: TileType, R: Renderer<T>> WFC<T, R> {
         rules: Vec<Box<dyn Rule<T>>>,
         renderer: Option<R>,
     ) -> Self {
+        Self::from_grid(Grid::new(width, height, possible_values), rules, renderer)
+    }
+
+    /// Runs on an existing grid, e.g. one made with [`Grid::new_3d`] or
+    /// [`Grid::from_topology`].
+    pub fn from_grid(mut grid: Grid<T>, rules: Vec<Box<dyn Rule<T>>>, renderer: Option<R>) -> Self {
+        grid.set_journaling(DEFAULT_MAX_BACKTRACK_DEPTH > 0);
         Self {
-            grid: Grid::new(width, height, possible_values),
+            grid,
             rules,
             renderer,
+            history: VecDeque::new(),
+            max_backtrack_depth: DEFAULT_MAX_BACKTRACK_DEPTH,
+            max_backtracks: DEFAULT_MAX_BACKTRACKS,
+            backtracks: 0,
+            rng: Box::new(StdRng::from_os_rng()),
+            selector: Box::new(EntropyQueue::new()),
+            initialized: false,
         }
     }
 
-    fn find_lowest_shannon_entropy_cell(&self) -> Option<(usize, usize)> {
-        let mut lowest_entropy = f64::INFINITY; // Start with a large value
-        let mut best_candidate = None;
+    /// Changes how the grid is laid out, see [`Lattice`].
+    pub fn with_lattice(mut self, lattice: Lattice) -> Self {
+        self.grid = self.grid.with_lattice(lattice);
+        self
+    }
+
+    /// Makes the grid wrap around along the given axes, see
+    /// [`Grid::with_wrapping`].
+    pub fn with_wrapping(mut self, wrap_x: bool, wrap_y: bool) -> Self {
+        self.grid = self.grid.with_wrapping(wrap_x, wrap_y);
+        self
+    }
 
-        for (x, row) in self.grid.get_cells().iter().enumerate() {
-            for (y, cell) in row.iter().enumerate() {
-                if cell.is_collapsed() {
-                    continue;
-                }
+    /// Makes generation reproducible: the same seed, tiles, rules and
+    /// presets always produce the same grid.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_rng(StdRng::seed_from_u64(seed))
+    }
 
-                let entropy = calculate_shannon_entropy(&cell.possible_values);
-                if entropy < lowest_entropy {
-                    lowest_entropy = entropy;
-                    best_candidate = Some((x, y));
-                }
+    /// Uses the given random number generator for every observation.
+    pub fn with_rng(mut self, rng: impl RngCore + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Changes how the next cell is chosen among cells of equal entropy, see
+    /// [`TieBreak`]. Selects by Shannon entropy, replacing any selector set
+    /// with [`WFC::with_selector`].
+    pub fn with_tie_break(self, tie_break: TieBreak) -> Self {
+        self.with_selector(EntropyQueue::new().with_tie_break(tie_break))
+    }
+
+    /// Changes how the next cell to observe is chosen, the lowest Shannon
+    /// entropy by default. See [`CellSelector`] for the alternatives.
+    pub fn with_selector(mut self, selector: impl CellSelector<T> + 'static) -> Self {
+        self.selector = Box::new(selector);
+        self
+    }
+
+    /// Limits how many decisions are remembered for backtracking. Older
+    /// decisions are committed once the limit is reached, a depth of 0
+    /// disables backtracking entirely.
+    pub fn with_max_backtrack_depth(mut self, depth: usize) -> Self {
+        self.max_backtrack_depth = depth;
+        self.grid.set_journaling(depth > 0);
+        if depth == 0 {
+            self.history.clear();
+        } else {
+            // Keep the latest decisions, like the window kept while solving
+            while self.history.len() > depth {
+                self.history.pop_front();
             }
+            self.commit_journal();
         }
+        self
+    }
 
-        best_candidate
+    /// Limits how many decisions may be undone over the whole run, 10,000 by
+    /// default. Once it is used up the next contradiction fails with
+    /// [`WfcError::BacktrackFailed`] instead of searching on, which could take
+    /// exponentially long on hard tile sets.
+    pub fn with_max_backtracks(mut self, max_backtracks: usize) -> Self {
+        self.max_backtracks = max_backtracks;
+        self
     }
 
-    pub fn run(&mut self) -> Result<(), String> {
-        while let Some((x, y)) = self.find_lowest_shannon_entropy_cell() {
-            let _cell = self.grid.collapse_cell(x, y)?;
+    /// How many decisions were undone so far.
+    pub fn backtracks(&self) -> usize {
+        self.backtracks
+    }
+
+    fn select_cell(&mut self) -> Option<usize> {
+        self.selector.select(&self.grid, self.rng.as_mut())
+    }
 
-            assert!(self.grid.get_cell(x, y).unwrap().is_collapsed());
-            self.propagate_all_constraints(vec![(x, y)]);
+    fn notify_changed(&mut self, cell: usize) {
+        self.selector
+            .cell_changed(&self.grid, cell, self.rng.as_mut());
+    }
 
+    pub fn run(&mut self) -> Result<(), WfcError> {
+        while self.step()?.is_some() {
             if let Some(renderer) = &self.renderer {
                 renderer.render(&self.grid);
             }
         }
-        Ok(())
+        match &self.renderer {
+            Some(renderer) => renderer.finish(&self.grid),
+            None => Ok(()),
+        }
     }
 
-    pub fn propagate_all_constraints(&mut self, start_cells: Vec<(usize, usize)>) {
-        let mut queue = start_cells;
-        while let Some((cx, cy)) = queue.pop() {
-            for rule in self.rules.iter() {
-                match rule.propagate_constraints(&mut self.grid, cx, cy) {
-                    Ok(mut affected_cells) => queue.append(&mut affected_cells),
-                    Err(e) => println!("Error propagating constraints: {}", e),
+    /// Observes the lowest entropy cell and propagates the result. Returns
+    /// `None` once every cell is collapsed.
+    pub fn step(&mut self) -> Result<Option<StepEvent<T>>, WfcError> {
+        if !self.initialized {
+            // Rules over the whole grid, like borders, apply before anything
+            // is observed
+            self.initialized = true;
+            self.propagate_all_constraints(Vec::new())?;
+        }
+        let Some(cell) = self.select_cell() else {
+            return Ok(None);
+        };
+        let journal_len = self.grid.journal_len();
+        let tile = self.grid.collapse_cell(cell, &mut self.rng)?;
+
+        assert!(self.grid.cell(cell).unwrap().is_collapsed());
+        self.record_decision(journal_len, cell, tile.clone());
+        match self.propagate_all_constraints(vec![cell]) {
+            Ok(constrained) => Ok(Some(StepEvent::Collapsed {
+                cell,
+                tile,
+                constrained,
+            })),
+            Err(reason @ (WfcError::Contradiction { .. } | WfcError::Unsatisfiable { .. })) => {
+                self.backtrack(reason.clone())?;
+                Ok(Some(StepEvent::Backtracked { cell, tile, reason }))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Iterates over the events of [`WFC::step`] until the grid is solved or
+    /// an error occurs. The renderer is not invoked.
+    pub fn steps(&mut self) -> Steps<'_, T, R> {
+        Steps {
+            wfc: self,
+            done: false,
+        }
+    }
+
+    fn record_decision(&mut self, journal_len: usize, cell: usize, tile: PossibleValue<T>) {
+        if self.max_backtrack_depth == 0 {
+            return;
+        }
+        if self.history.len() >= self.max_backtrack_depth {
+            self.history.pop_front();
+        }
+        self.history.push_back(DecisionPoint {
+            journal_len,
+            cell,
+            tile,
+        });
+        self.commit_journal();
+    }
+
+    fn commit_journal(&mut self) {
+        // Changes made before the oldest remembered decision are committed
+        let Some(oldest) = self.history.front() else {
+            return;
+        };
+        let committed = oldest.journal_len;
+        if committed > 0 {
+            self.grid.forget_journal(committed);
+            for decision in self.history.iter_mut() {
+                decision.journal_len -= committed;
+            }
+        }
+    }
+
+    /// Rolls back to the most recent decision, bans the tile that was chosen
+    /// there and propagates the ban. Keeps unwinding while that still leads
+    /// to a contradiction.
+    fn backtrack(&mut self, mut err: WfcError) -> Result<(), WfcError> {
+        while let Some(decision) = self.history.pop_back() {
+            if self.backtracks >= self.max_backtracks {
+                self.history.clear();
+                break;
+            }
+            self.backtracks += 1;
+            for cell in self.grid.undo(decision.journal_len) {
+                self.notify_changed(cell);
+            }
+            let cell = self.grid.cell_mut(decision.cell).unwrap();
+            cell.ban(&decision.tile);
+            if cell.is_contradiction() {
+                err = WfcError::Contradiction {
+                    cell: decision.cell,
+                    rule: "backtracking".to_string(),
+                };
+                continue;
+            }
+
+            match self.propagate_all_constraints(vec![decision.cell]) {
+                Ok(_) => return Ok(()),
+                Err(e @ (WfcError::Contradiction { .. } | WfcError::Unsatisfiable { .. })) => {
+                    err = e
                 }
+                Err(e) => return Err(e),
             }
         }
+        Err(WfcError::BacktrackFailed(Box::new(err)))
     }
 
-    pub fn preset_tile(&mut self, value: PossibleValue<T>, x: usize, y: usize) {
-        if let Some(cell) = self.grid.get_cell_mut(x, y) {
-            cell.possible_values = std::iter::once(value).collect();
-            println!("Presetting cell ({}, {}) to {:?}", x, y, cell.possible_values);
+    /// Propagates constraints until nothing changes, returns every cell whose
+    /// possible values were narrowed.
+    pub fn propagate_all_constraints(
+        &mut self,
+        start_cells: Vec<usize>,
+    ) -> Result<Vec<usize>, WfcError> {
+        let mut constrained = Vec::new();
+        let mut seen = HashSet::new();
+        for &cell in &start_cells {
+            self.notify_changed(cell);
+        }
+        let mut queue = start_cells;
+        loop {
+            while let Some(current) = queue.pop() {
+                for rule in self.rules.iter() {
+                    let affected_cells = rule.propagate_constraints(&mut self.grid, current)?;
+                    for cell in affected_cells {
+                        self.selector
+                            .cell_changed(&self.grid, cell, self.rng.as_mut());
+                        if seen.insert(cell) {
+                            constrained.push(cell);
+                        }
+                        queue.push(cell);
+                    }
+                }
+            }
 
-            self.propagate_all_constraints(vec![(x, y)]);
+            // Before the first observation the layout may still change
+            let global_rules = if self.initialized {
+                &self.rules[..]
+            } else {
+                &[]
+            };
+            for rule in global_rules {
+                let affected_cells = rule.propagate_global_constraints(&mut self.grid)?;
+                for cell in affected_cells {
+                    self.selector
+                        .cell_changed(&self.grid, cell, self.rng.as_mut());
+                    if seen.insert(cell) {
+                        constrained.push(cell);
+                    }
+                    queue.push(cell);
+                }
+                if !queue.is_empty() {
+                    // Settle the local rules again before the next global check
+                    break;
+                }
+            }
+            if queue.is_empty() {
+                return Ok(constrained);
+            }
         }
     }
 
+    pub fn preset_tile(
+        &mut self,
+        value: PossibleValue<T>,
+        x: usize,
+        y: usize,
+    ) -> Result<(), WfcError> {
+        self.preset_tile_3d(value, x, y, 0)
+    }
+
+    pub fn preset_tile_3d(
+        &mut self,
+        value: PossibleValue<T>,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<(), WfcError> {
+        let cell = self
+            .grid
+            .index_3d(x, y, z)
+            .ok_or(WfcError::OutOfBounds { x, y, z })?;
+        self.preset_cell(value, cell)
+    }
+
+    /// Presets the cell at `index`, see [`Grid::index`].
+    pub fn preset_cell(&mut self, value: PossibleValue<T>, index: usize) -> Result<(), WfcError> {
+        self.grid
+            .cell_mut(index)
+            .ok_or(WfcError::UnknownCell { cell: index })?
+            .set(&value)?;
+
+        self.propagate_all_constraints(vec![index])?;
+        Ok(())
+    }
+
+    /// Restricts generation to the cells for which `is_active(x, y, z)` is
+    /// true, e.g. an island outline or an L-shaped room. See
+    /// [`Grid::apply_mask`] for what happens to the other cells.
+    pub fn mask_cells(
+        &mut self,
+        is_active: impl Fn(usize, usize, usize) -> bool,
+        mode: InactiveCells<T>,
+    ) -> Result<(), WfcError> {
+        let changed = self.grid.apply_mask(is_active, &mode)?;
+        self.propagate_all_constraints(changed)?;
+        Ok(())
+    }
+
     pub fn debug_render(&self) {
         if let Some(renderer) = &self.renderer {
             renderer.render(&self.grid);
@@ -108,3 +393,137 @@ impl<T: TileType, R: Renderer<T>> WFC<T, R> {
         }
     }
 }
+
+pub struct Steps<'a, T: TileType, R: Renderer<T>> {
+    wfc: &'a mut WFC<T, R>,
+    done: bool,
+}
+
+impl<T: TileType, R: Renderer<T>> Steps<'_, T, R> {
+    /// The grid as it is after the last yielded event.
+    pub fn grid(&self) -> &Grid<T> {
+        &self.wfc.grid
+    }
+}
+
+impl<T: TileType, R: Renderer<T>> Iterator for Steps<'_, T, R> {
+    type Item = Result<StepEvent<T>, WfcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.wfc.step() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        adjacency_graph::AdjacencyGraph, renderer::AsciiRenderer,
+        rules::adjacency_rule::AdjacencyRule, selection::RandomOrder, types::Tile,
+    };
+
+    /// Three colors of which no two equal ones may touch.
+    fn three_colors(size: usize) -> WFC<char, AsciiRenderer> {
+        let tiles =
+            [('R', "Red"), ('G', "Green"), ('B', "Blue")].map(|(id, name)| Tile::new(id, name, 1));
+        let mut graph = AdjacencyGraph::new();
+        graph.add_adjacency(&tiles[0], &tiles[1]);
+        graph.add_adjacency(&tiles[1], &tiles[2]);
+        graph.add_adjacency(&tiles[0], &tiles[2]);
+        WFC::new(
+            size,
+            size,
+            tiles.into_iter().collect(),
+            vec![Box::new(AdjacencyRule::new(graph))],
+            None,
+        )
+    }
+
+    fn assert_solved(grid: &Grid<char>) {
+        for cell in 0..grid.len() {
+            let tile = grid.cell(cell).unwrap().get_collapsed_value().unwrap();
+            for neighbor in grid.get_neighbors(cell) {
+                assert_ne!(
+                    tile,
+                    grid.cell(neighbor).unwrap().get_collapsed_value().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_grid() {
+        let run = |seed| {
+            let mut wfc = three_colors(8).with_seed(seed);
+            wfc.run().unwrap();
+            wfc.grid.snapshot()
+        };
+        assert_eq!(run(7), run(7));
+        assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn backtracking_recovers_from_contradictions() {
+        // Observing in random order paints some seeds into a corner, which
+        // backtracking has to get out of
+        let mut backtracks = 0;
+        for seed in 0..20 {
+            let mut wfc = three_colors(6)
+                .with_seed(seed)
+                .with_selector(RandomOrder::new());
+            wfc.run().unwrap();
+            assert_solved(&wfc.grid);
+            backtracks += wfc.backtracks();
+        }
+        assert!(backtracks > 0);
+    }
+
+    #[test]
+    fn backtracking_stops_once_the_budget_is_used_up() {
+        let mut wfc = three_colors(20)
+            .with_seed(0)
+            .with_selector(RandomOrder::new())
+            .with_max_backtracks(100);
+        assert!(matches!(wfc.run(), Err(WfcError::BacktrackFailed(_))));
+        assert_eq!(wfc.backtracks(), 100);
+    }
+
+    #[test]
+    fn lowering_the_backtrack_depth_keeps_the_latest_decisions() {
+        let mut wfc = three_colors(6).with_seed(1);
+        for _ in 0..5 {
+            wfc.step().unwrap();
+        }
+        let latest: Vec<usize> = wfc
+            .history
+            .iter()
+            .skip(3)
+            .map(|decision| decision.cell)
+            .collect();
+        let mut wfc = wfc.with_max_backtrack_depth(2);
+        assert_eq!(
+            wfc.history
+                .iter()
+                .map(|decision| decision.cell)
+                .collect::<Vec<_>>(),
+            latest
+        );
+        // The journal still rolls back to the oldest remembered decision
+        let decision = wfc.history.pop_front().unwrap();
+        wfc.grid.undo(decision.journal_len);
+        assert!(!wfc.grid.cell(decision.cell).unwrap().is_collapsed());
+    }
+}